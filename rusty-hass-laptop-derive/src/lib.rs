@@ -0,0 +1,288 @@
+//! `#[derive(Sensor)]` for rusty-hass-laptop sensors.
+//!
+//! ```ignore
+//! #[derive(Sensor, Debug)]
+//! #[sensor(
+//!     provider = "CpuProvider",
+//!     current = "cpu_speed",
+//!     id = "cpu_freq",
+//!     name = "CPU Frequency",
+//!     device_class = "frequency",
+//!     unit = "MHz",
+//!     icon = "mdi:sine-wave"
+//! )]
+//! pub struct CpuFreq {
+//!     pub state: u64,
+//! }
+//! ```
+//!
+//! The struct needs a `state` field, its type becomes the `StateType` of the
+//! sensor. Every other field is initialised with `Default::default()`.
+//!
+//! Both `Sensor<Provider>` and the object safe `DynSensor` are implemented.
+//!
+//! Scheduling is configured with `interval` (seconds between polls),
+//! `report = "always" | "on_change"`, or one of `threshold` (absolute) /
+//! `threshold_percent` to only report significant changes, and `heartbeat`
+//! (seconds) to force a report once in a while.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Fields, Lit, LitInt, LitStr, Path};
+
+#[proc_macro_derive(Sensor, attributes(sensor))]
+pub fn derive_sensor(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct SensorAttrs {
+    provider: Option<Path>,
+    current: Option<Path>,
+    id: Option<LitStr>,
+    name: Option<LitStr>,
+    device_class: Option<LitStr>,
+    unit: Option<LitStr>,
+    icon: Option<LitStr>,
+    icon_fn: Option<Path>,
+    state_class: Option<LitStr>,
+    entity_category: Option<LitStr>,
+    binary: bool,
+    interval: Option<LitInt>,
+    report: Option<LitStr>,
+    threshold: Option<f64>,
+    threshold_percent: Option<f64>,
+    heartbeat: Option<LitInt>,
+}
+
+impl SensorAttrs {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut attrs = SensorAttrs::default();
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("sensor")) {
+            attr.parse_nested_meta(|meta| {
+                let key = meta
+                    .path
+                    .get_ident()
+                    .map(|i| i.to_string())
+                    .unwrap_or_default();
+                match key.as_str() {
+                    "provider" => attrs.provider = Some(meta.value()?.parse::<LitStr>()?.parse()?),
+                    "current" => attrs.current = Some(meta.value()?.parse::<LitStr>()?.parse()?),
+                    "icon_fn" => attrs.icon_fn = Some(meta.value()?.parse::<LitStr>()?.parse()?),
+                    "id" => attrs.id = Some(meta.value()?.parse()?),
+                    "name" => attrs.name = Some(meta.value()?.parse()?),
+                    "device_class" => attrs.device_class = Some(meta.value()?.parse()?),
+                    "unit" => attrs.unit = Some(meta.value()?.parse()?),
+                    "icon" => attrs.icon = Some(meta.value()?.parse()?),
+                    "state_class" => attrs.state_class = Some(meta.value()?.parse()?),
+                    "entity_category" => attrs.entity_category = Some(meta.value()?.parse()?),
+                    "binary" => attrs.binary = true,
+                    "interval" => attrs.interval = Some(meta.value()?.parse()?),
+                    "report" => attrs.report = Some(meta.value()?.parse()?),
+                    "threshold" => attrs.threshold = Some(parse_number(meta.value()?.parse()?)?),
+                    "threshold_percent" => {
+                        attrs.threshold_percent = Some(parse_number(meta.value()?.parse()?)?)
+                    }
+                    "heartbeat" => attrs.heartbeat = Some(meta.value()?.parse()?),
+                    _ => return Err(meta.error("unknown sensor attribute")),
+                }
+                Ok(())
+            })?;
+        }
+        Ok(attrs)
+    }
+}
+
+fn parse_number(lit: Lit) -> syn::Result<f64> {
+    match &lit {
+        Lit::Int(int) => int.base10_parse(),
+        Lit::Float(float) => float.base10_parse(),
+        _ => Err(Error::new_spanned(lit, "expected a number")),
+    }
+}
+
+fn report_policy(
+    attrs: &SensorAttrs,
+    sensors: &proc_macro2::TokenStream,
+) -> syn::Result<proc_macro2::TokenStream> {
+    match (&attrs.report, attrs.threshold, attrs.threshold_percent) {
+        (_, Some(_), Some(_)) => Err(Error::new(
+            Span::call_site(),
+            "`threshold` and `threshold_percent` are mutually exclusive",
+        )),
+        (None, Some(threshold), None) => {
+            Ok(quote!(#sensors::ReportPolicy::AbsoluteThreshold(#threshold)))
+        }
+        (None, None, Some(percent)) => {
+            Ok(quote!(#sensors::ReportPolicy::PercentThreshold(#percent)))
+        }
+        (Some(report), None, None) => match report.value().as_str() {
+            "always" => Ok(quote!(#sensors::ReportPolicy::Always)),
+            "on_change" => Ok(quote!(#sensors::ReportPolicy::OnChange)),
+            _ => Err(Error::new_spanned(
+                report,
+                "expected `always` or `on_change`",
+            )),
+        },
+        (None, None, None) => Ok(quote!(#sensors::ReportPolicy::Always)),
+        (Some(report), _, _) => Err(Error::new_spanned(
+            report,
+            "`report` can't be combined with a threshold",
+        )),
+    }
+}
+
+fn required<T>(value: Option<T>, name: &str) -> syn::Result<T> {
+    value.ok_or_else(|| {
+        Error::new(
+            Span::call_site(),
+            format!("missing `#[sensor({} = \"...\")]` attribute", name),
+        )
+    })
+}
+
+fn optional_string(value: &Option<LitStr>) -> proc_macro2::TokenStream {
+    match value {
+        Some(lit) => quote!(::std::option::Option::Some(::std::string::String::from(#lit))),
+        None => quote!(::std::option::Option::None),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "Sensor can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "Sensor can only be derived for structs",
+            ))
+        }
+    };
+    let state_type = fields
+        .iter()
+        .find(|f| f.ident.as_ref().is_some_and(|i| i == "state"))
+        .map(|f| f.ty.clone())
+        .ok_or_else(|| Error::new_spanned(&input.ident, "Sensor needs a `state` field"))?;
+    let field_names = fields.iter().map(|f| f.ident.as_ref().unwrap());
+
+    let attrs = SensorAttrs::parse(&input)?;
+    let provider = required(attrs.provider.clone(), "provider")?;
+    let current = required(attrs.current.clone(), "current")?;
+    let id = required(attrs.id.clone(), "id")?;
+    let name = required(attrs.name.clone(), "name")?;
+    let device_class = optional_string(&attrs.device_class);
+    let unit = optional_string(&attrs.unit);
+    let icon = optional_string(&attrs.icon);
+    let state_class = optional_string(&attrs.state_class);
+    let entity_category = optional_string(&attrs.entity_category);
+    let update_icon = match &attrs.icon_fn {
+        Some(icon_fn) => quote!(::std::option::Option::Some(#icon_fn(&self.state))),
+        None => icon.clone(),
+    };
+    let sensors = quote!(::rusty_hass_laptop::sensors);
+    let sensor_type = if attrs.binary {
+        quote!(#sensors::SensorType::BinarySensor)
+    } else {
+        quote!(#sensors::SensorType::Sensor)
+    };
+
+    let policy = report_policy(&attrs, &sensors)?;
+    let interval = match &attrs.interval {
+        Some(secs) => quote!(::std::time::Duration::from_secs(#secs)),
+        None => quote!(#sensors::DEFAULT_POLL_INTERVAL),
+    };
+    let heartbeat = match &attrs.heartbeat {
+        Some(secs) => quote!(::std::option::Option::Some(::std::time::Duration::from_secs(#secs))),
+        None => quote!(::std::option::Option::None),
+    };
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #sensors::Sensor<#provider> for #ident #ty_generics #where_clause {
+            type StateType = #state_type;
+
+            fn new() -> Self {
+                #ident {
+                    #(#field_names: ::std::default::Default::default(),)*
+                }
+            }
+
+            fn get_current(provider: &#provider) -> ::std::result::Result<Self::StateType, &'static str> {
+                #current(provider)
+            }
+
+            fn get_register_info(&self) -> #sensors::SensorData<Self::StateType> {
+                #sensors::SensorData::RegisterSensor::<Self::StateType> {
+                    data: #sensors::SensorRegisterData {
+                        r#type: #sensor_type,
+                        unique_id: ::std::string::String::from(#id),
+                        name: ::std::string::String::from(#name),
+                        state: ::std::clone::Clone::clone(&self.state),
+                        device_class: #device_class,
+                        icon: #icon,
+                        unit_of_measurement: #unit,
+                        state_class: #state_class,
+                        entity_category: #entity_category,
+                    },
+                }
+            }
+
+            fn get_update_info(&self) -> #sensors::SensorData<Self::StateType> {
+                #sensors::SensorData::UpdateSensorStates::<Self::StateType> {
+                    data: #sensors::SensorUpdateData {
+                        r#type: #sensor_type,
+                        unique_id: ::std::string::String::from(#id),
+                        state: ::std::clone::Clone::clone(&self.state),
+                        icon: #update_icon,
+                        available: ::std::option::Option::None,
+                    },
+                }
+            }
+        }
+
+        impl #impl_generics #sensors::DynSensor for #ident #ty_generics #where_clause {
+            fn unique_id(&self) -> ::std::string::String {
+                ::std::string::String::from(#id)
+            }
+
+            fn register_info(&self) -> #sensors::DynSensorData {
+                #sensors::SensorData::into_dyn(
+                    <Self as #sensors::Sensor<#provider>>::get_register_info(self),
+                )
+            }
+
+            fn update_info(&self) -> #sensors::DynSensorData {
+                #sensors::SensorData::into_dyn(
+                    <Self as #sensors::Sensor<#provider>>::get_update_info(self),
+                )
+            }
+
+            fn poll_interval(&self) -> ::std::time::Duration {
+                #interval
+            }
+
+            fn report_policy(&self) -> #sensors::ReportPolicy {
+                #policy
+            }
+
+            fn heartbeat(&self) -> ::std::option::Option<::std::time::Duration> {
+                #heartbeat
+            }
+        }
+    })
+}
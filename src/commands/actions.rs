@@ -0,0 +1,179 @@
+//! Actions declared in the config, that Home Assistant can trigger with a
+//! `command_<name>` notification. Only the declared actions can be run.
+
+use super::CommandHandler;
+use crate::{
+    hass::{websocket::PushMessage, Client},
+    logind::{self, ManagerProxy},
+};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{collections::BTreeMap, sync::Arc, time::Duration};
+use tokio::{process::Command, time};
+use zbus::Connection;
+
+/// Event fired on Home Assistant with the result of each command
+const RESULT_EVENT: &str = "rusty_hass_laptop_action";
+
+fn default_script_timeout() -> u64 {
+    60
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum Action {
+    /// Lock the graphical session through logind
+    Lock,
+    Suspend,
+    Poweroff,
+    /// Run a program, with the arguments given in the config only
+    Script {
+        path: String,
+        #[serde(default)]
+        args: Vec<String>,
+        /// Seconds before the program is killed
+        #[serde(default = "default_script_timeout")]
+        timeout: u64,
+    },
+}
+
+impl Action {
+    /// Run the action, returning the output of scripts
+    pub async fn run(&self) -> Result<Option<String>, String> {
+        match self {
+            Action::Lock => {
+                let connection = Connection::system().await.map_err(|e| e.to_string())?;
+                let session = logind::display_session(&connection)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                session.lock().await.map_err(|e| e.to_string())?;
+                Ok(None)
+            }
+            Action::Suspend | Action::Poweroff => {
+                let connection = Connection::system().await.map_err(|e| e.to_string())?;
+                let manager = ManagerProxy::new(&connection)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                match self {
+                    Action::Suspend => manager.suspend(false).await,
+                    _ => manager.power_off(false).await,
+                }
+                .map_err(|e| e.to_string())?;
+                Ok(None)
+            }
+            Action::Script {
+                path,
+                args,
+                timeout,
+            } => {
+                // Dropping the output future on timeout kills the program
+                let output = Command::new(path).args(args).kill_on_drop(true).output();
+                let output = time::timeout(Duration::from_secs(*timeout), output)
+                    .await
+                    .map_err(|_| format!("killed after {}s", timeout))?
+                    .map_err(|e| e.to_string())?;
+                let stdout = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                if output.status.success() {
+                    Ok(Some(stdout))
+                } else {
+                    Err(format!("{}: {}", output.status, stdout))
+                }
+            }
+        }
+    }
+}
+
+/// Run the allowlisted actions and report their result as an event
+pub struct ActionHandler {
+    client: Client,
+    actions: Arc<BTreeMap<String, Action>>,
+}
+
+impl ActionHandler {
+    pub fn new(client: Client, actions: BTreeMap<String, Action>) -> Self {
+        ActionHandler {
+            client,
+            actions: Arc::new(actions),
+        }
+    }
+}
+
+async fn run(actions: &BTreeMap<String, Action>, name: &str) -> Value {
+    let result = match actions.get(name) {
+        Some(action) => action.run().await,
+        None => Err(String::from("action not allowed")),
+    };
+    match result {
+        Ok(output) => json!({"action": name, "success": true, "output": output}),
+        Err(error) => json!({"action": name, "success": false, "error": error}),
+    }
+}
+
+#[async_trait]
+impl CommandHandler for ActionHandler {
+    async fn handle(&self, message: &PushMessage) -> bool {
+        let name = match message.command() {
+            Some(name) => name,
+            None => return false,
+        };
+        // Run in the background, so a slow script doesn't hold the next messages
+        let name = name.to_string();
+        let client = self.client.clone();
+        let actions = self.actions.clone();
+        tokio::spawn(async move {
+            let result = run(&actions, &name).await;
+            println!("Command {}: {}", name, result);
+            if let Err(err) = client.fire_event(RESULT_EVENT, result).await {
+                println!("Unable to report command result: {}", err);
+            }
+        });
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Action;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn deserialize_test() {
+        let actions: BTreeMap<String, Action> = serde_json::from_str(
+            r#"{"lock":{"type":"lock"},"backup":{"type":"script","path":"/usr/bin/backup","args":["--quick"]}}"#,
+        )
+        .unwrap();
+        assert_eq!(actions["lock"], Action::Lock);
+        assert_eq!(
+            actions["backup"],
+            Action::Script {
+                path: String::from("/usr/bin/backup"),
+                args: vec![String::from("--quick")],
+                timeout: 60,
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn script_test() {
+        let echo = Action::Script {
+            path: String::from("echo"),
+            args: vec![String::from("done")],
+            timeout: 5,
+        };
+        assert_eq!(echo.run().await, Ok(Some(String::from("done"))));
+        let fail = Action::Script {
+            path: String::from("false"),
+            args: Vec::new(),
+            timeout: 5,
+        };
+        assert!(fail.run().await.is_err());
+        let slow = Action::Script {
+            path: String::from("sleep"),
+            args: vec![String::from("10")],
+            timeout: 1,
+        };
+        assert_eq!(slow.run().await, Err(String::from("killed after 1s")));
+    }
+}
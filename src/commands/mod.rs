@@ -0,0 +1,105 @@
+//! Messages and commands pushed by Home Assistant to the laptop
+
+use crate::hass::{
+    websocket::{ChannelError, PushMessage},
+    Client,
+};
+use async_trait::async_trait;
+use std::time::Duration;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+
+pub mod actions;
+
+/// Delay before reconnecting to the push notification channel
+const RECONNECT_DELAY: Duration = Duration::from_secs(30);
+
+#[async_trait]
+pub trait CommandHandler: Send + Sync {
+    /// Handle a message pushed by Home Assistant, `true` if it was consumed
+    async fn handle(&self, message: &PushMessage) -> bool;
+}
+
+/// Hand each pushed message to the first handler accepting it
+#[derive(Default)]
+pub struct Dispatcher {
+    handlers: Vec<Box<dyn CommandHandler>>,
+}
+
+impl Dispatcher {
+    pub fn new() -> Self {
+        Dispatcher::default()
+    }
+
+    pub fn add(&mut self, handler: Box<dyn CommandHandler>) {
+        self.handlers.push(handler);
+    }
+
+    pub async fn dispatch(&self, message: &PushMessage) -> bool {
+        for handler in self.handlers.iter() {
+            if handler.handle(message).await {
+                return true;
+            }
+        }
+        println!("Unhandled push message: {}", message.message);
+        false
+    }
+}
+
+/// Receive the pushed messages until shutdown, reconnecting when needed
+pub async fn listen(client: Client, dispatcher: Dispatcher, shutdown: CancellationToken) {
+    loop {
+        let err = tokio::select! {
+            _ = shutdown.cancelled() => return,
+            Err(err) = receive(&client, &dispatcher) => err,
+        };
+        println!("Push notification channel lost: {}", err);
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            _ = time::sleep(RECONNECT_DELAY) => (),
+        }
+    }
+}
+
+async fn receive(client: &Client, dispatcher: &Dispatcher) -> Result<(), ChannelError> {
+    let mut channel = client.push_notification_channel().await?;
+    loop {
+        let message = channel.next().await?;
+        dispatcher.dispatch(&message).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommandHandler, Dispatcher};
+    use crate::hass::websocket::PushMessage;
+    use async_trait::async_trait;
+
+    struct Named(&'static str);
+
+    #[async_trait]
+    impl CommandHandler for Named {
+        async fn handle(&self, message: &PushMessage) -> bool {
+            message.command() == Some(self.0)
+        }
+    }
+
+    fn message(message: &str) -> PushMessage {
+        PushMessage {
+            message: String::from(message),
+            title: None,
+            data: Default::default(),
+            hass_confirm_id: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn dispatch_test() {
+        let mut dispatcher = Dispatcher::new();
+        dispatcher.add(Box::new(Named("lock")));
+        dispatcher.add(Box::new(Named("suspend")));
+        assert!(dispatcher.dispatch(&message("command_suspend")).await);
+        assert!(!dispatcher.dispatch(&message("command_poweroff")).await);
+        assert!(!dispatcher.dispatch(&message("Hello")).await);
+    }
+}
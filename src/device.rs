@@ -0,0 +1,157 @@
+//! Identity and hardware description of the laptop, from the machine id and
+//! the DMI tables exposed in sysfs, or the device tree on ARM boards.
+
+use hmac_sha256::HMAC;
+use std::fs;
+
+const DMI_DIR: &str = "/sys/class/dmi/id";
+const DEVICE_TREE_DIR: &str = "/proc/device-tree";
+const MACHINE_ID_PATH: &str = "/etc/machine-id";
+/// Salt of the machine id, so the device id can't be correlated with the
+/// machine id used elsewhere (see `sd_id128_get_machine_app_specific`)
+const APP_ID: [u8; 16] = [
+    0xa4, 0x05, 0xc9, 0x5d, 0x05, 0x53, 0xa9, 0x90, 0x1a, 0x29, 0xf1, 0x6d, 0x4c, 0xbd, 0x04, 0x89,
+];
+
+/// Values left by vendors that didn't fill the DMI tables
+const PLACEHOLDERS: [&str; 9] = [
+    "To be filled by O.E.M.",
+    "To Be Filled By O.E.M.",
+    "System manufacturer",
+    "System Product Name",
+    "System Version",
+    "Default string",
+    "Not Applicable",
+    "None",
+    "N/A",
+];
+
+fn clean(value: &str) -> Option<String> {
+    let value = value.trim_matches(|c: char| c.is_whitespace() || c == '\0');
+    (!value.is_empty() && !PLACEHOLDERS.contains(&value)).then(|| value.to_string())
+}
+
+/// Content of a DMI field, `None` when missing, empty or a placeholder
+pub fn dmi_field(field: &str) -> Option<String> {
+    clean(&fs::read_to_string(format!("{}/{}", DMI_DIR, field)).ok()?)
+}
+
+fn device_tree_field(field: &str) -> Option<String> {
+    clean(&fs::read_to_string(format!("{}/{}", DEVICE_TREE_DIR, field)).ok()?)
+}
+
+/// Vendor of the first `vendor,device` entry of the device tree
+/// `compatible` list
+fn vendor_from_compatible(compatible: &str) -> Option<String> {
+    let (vendor, _) = compatible.split('\0').next()?.split_once(',')?;
+    clean(vendor)
+}
+
+/// Some vendors (Lenovo) put the product name in `product_version`, and
+/// a machine type in `product_name`
+fn model_from(name: Option<String>, version: Option<String>) -> Option<String> {
+    match (name, version) {
+        (Some(name), Some(version))
+            if version.contains(' ') && version.chars().any(char::is_alphabetic) =>
+        {
+            Some(format!("{} ({})", version, name))
+        }
+        (name, _) => name,
+    }
+}
+
+/// Same derivation as systemd: HMAC-SHA256 of the app id keyed with the
+/// machine id, truncated and formatted as a v4 UUID
+fn app_specific_id(machine_id: &str, app_id: &[u8; 16]) -> Option<String> {
+    let machine_id = machine_id.trim();
+    if machine_id.len() != 32 {
+        return None;
+    }
+    let key = (0..16)
+        .map(|i| u8::from_str_radix(machine_id.get(i * 2..i * 2 + 2)?, 16).ok())
+        .collect::<Option<Vec<u8>>>()?;
+    let mut id = HMAC::mac(app_id, key);
+    id[6] = (id[6] & 0x0f) | 0x40;
+    id[8] = (id[8] & 0x3f) | 0x80;
+    Some(
+        id[..16]
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect(),
+    )
+}
+
+/// Device id derived from the machine id, which survives hostname changes
+pub fn device_id() -> Option<String> {
+    app_specific_id(&fs::read_to_string(MACHINE_ID_PATH).ok()?, &APP_ID)
+}
+
+/// `<hostname>_<os>`, the device id of the first versions
+pub fn legacy_device_id() -> String {
+    format!(
+        "{}_{}",
+        sys_info::hostname().unwrap(),
+        sys_info::os_type().unwrap()
+    )
+}
+
+pub fn manufacturer() -> Option<String> {
+    dmi_field("sys_vendor").or_else(|| vendor_from_compatible(&device_tree_field("compatible")?))
+}
+
+pub fn model() -> Option<String> {
+    model_from(dmi_field("product_name"), dmi_field("product_version"))
+        .or_else(|| device_tree_field("model"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{app_specific_id, clean, model_from, vendor_from_compatible, APP_ID};
+
+    #[test]
+    fn device_id_test() {
+        assert_eq!(
+            app_specific_id("3d1219c7c4c5404aaa1f6d2a48adfda4\n", &APP_ID).as_deref(),
+            Some("c84a052faa8e4846a358ffb482820be0")
+        );
+        assert_eq!(app_specific_id("uninitialized\n", &APP_ID), None);
+    }
+
+    #[test]
+    fn clean_test() {
+        assert_eq!(clean("LENOVO\n"), Some(String::from("LENOVO")));
+        assert_eq!(
+            clean("Raspberry Pi 4 Model B Rev 1.4\0"),
+            Some(String::from("Raspberry Pi 4 Model B Rev 1.4"))
+        );
+        assert_eq!(clean("To be filled by O.E.M.\n"), None);
+        assert_eq!(clean(" \n"), None);
+    }
+
+    #[test]
+    fn model_test() {
+        let some = |value: &str| Some(value.to_string());
+        assert_eq!(
+            model_from(some("20XW004XUS"), some("ThinkPad X1 Carbon Gen 9")),
+            some("ThinkPad X1 Carbon Gen 9 (20XW004XUS)")
+        );
+        assert_eq!(
+            model_from(some("XPS 13 9310"), some("1.0")),
+            some("XPS 13 9310")
+        );
+        assert_eq!(
+            model_from(some("Framework Laptop"), None),
+            some("Framework Laptop")
+        );
+        assert_eq!(model_from(None, some("ThinkPad X1 Carbon Gen 9")), None);
+    }
+
+    #[test]
+    fn compatible_test() {
+        assert_eq!(
+            vendor_from_compatible("raspberrypi,4-model-b\0brcm,bcm2711\0"),
+            Some(String::from("raspberrypi"))
+        );
+        assert_eq!(vendor_from_compatible("simple-board\0"), None);
+    }
+}
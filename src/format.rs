@@ -0,0 +1,104 @@
+//! Config file formats, picked from the file extension.
+
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// File names looked up in the config directories, by order of preference
+pub const CONFIG_FILE_NAMES: [&str; 4] =
+    ["config.json", "config.toml", "config.yaml", "config.yml"];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// JSON unless the extension says otherwise
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+
+    pub fn parse(self, content: &str) -> Result<Value, &'static str> {
+        match self {
+            ConfigFormat::Json => serde_json::from_str(content).map_err(|_| "Invalid JSON config"),
+            ConfigFormat::Toml => toml::from_str(content).map_err(|_| "Invalid TOML config"),
+            ConfigFormat::Yaml => {
+                serde_norway::from_str(content).map_err(|_| "Invalid YAML config")
+            }
+        }
+    }
+
+    pub fn serialize<T: Serialize>(self, value: &T) -> Result<String, &'static str> {
+        match self {
+            ConfigFormat::Json => {
+                serde_json::to_string_pretty(value).map_err(|_| "Unable to serialize the config")
+            }
+            ConfigFormat::Toml => {
+                toml::to_string_pretty(value).map_err(|_| "Unable to serialize the config")
+            }
+            ConfigFormat::Yaml => {
+                serde_norway::to_string(value).map_err(|_| "Unable to serialize the config")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigFormat;
+    use crate::Config;
+    use std::path::Path;
+
+    #[test]
+    fn from_path_test() {
+        let format = |path: &str| ConfigFormat::from_path(Path::new(path));
+        assert_eq!(
+            format("/etc/rusty-hass-laptop/config.json"),
+            ConfigFormat::Json
+        );
+        assert_eq!(format("config.toml"), ConfigFormat::Toml);
+        assert_eq!(format("config.yml"), ConfigFormat::Yaml);
+        assert_eq!(format("config"), ConfigFormat::Json);
+    }
+
+    #[test]
+    fn round_trip_test() {
+        let config = Config {
+            hass_address: Some(String::from("http://hass.local:8123")),
+            ..Default::default()
+        };
+        for format in [ConfigFormat::Json, ConfigFormat::Toml, ConfigFormat::Yaml] {
+            let content = format.serialize(&config).unwrap();
+            let read: Config = serde_json::from_value(format.parse(&content).unwrap()).unwrap();
+            assert_eq!(read.hass_address, config.hass_address, "{:?}", format);
+            assert_eq!(read.device_id, config.device_id, "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn toml_test() {
+        let content = r#"
+# Hand written config
+hass_address = "http://hass.local:8123"
+auth_token = "secret"
+
+[actions.lock]
+type = "lock"
+
+[location]
+enabled = true
+"#;
+        let config: Config =
+            serde_json::from_value(ConfigFormat::Toml.parse(content).unwrap()).unwrap();
+        assert_eq!(config.auth_token.as_deref(), Some("secret"));
+        assert!(config.actions.contains_key("lock"));
+        assert!(config.location.enabled);
+    }
+}
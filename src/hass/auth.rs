@@ -0,0 +1,254 @@
+//! Home Assistant login flow, for users without a long-lived token: the
+//! browser is sent to `/auth/authorize`, which redirects back to a listener on
+//! localhost with a code exchanged on `/auth/token` for a refresh token.
+
+use reqwest::Url;
+use serde::Deserialize;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, Read},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+};
+
+/// Refresh the access token this long before it expires
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+#[derive(Debug)]
+pub enum LoginError {
+    Io(io::Error),
+    Http(reqwest::Error),
+    /// Authorization refused, or a redirect that doesn't match the request
+    Denied(String),
+}
+
+impl fmt::Display for LoginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoginError::Io(err) => write!(f, "redirect listener error: {}", err),
+            LoginError::Http(err) => write!(f, "token request failed: {}", err),
+            LoginError::Denied(reason) => write!(f, "login denied: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LoginError {}
+
+impl From<io::Error> for LoginError {
+    fn from(err: io::Error) -> Self {
+        LoginError::Io(err)
+    }
+}
+
+impl From<reqwest::Error> for LoginError {
+    fn from(err: reqwest::Error) -> Self {
+        LoginError::Http(err)
+    }
+}
+
+/// Response of `/auth/token`, the refresh token is only sent for a code
+#[derive(Deserialize, Debug)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub expires_in: u64,
+    pub refresh_token: Option<String>,
+}
+
+/// Access token obtained from the refresh token
+#[derive(Debug, Clone)]
+pub struct AccessToken {
+    pub token: String,
+    expires: Instant,
+}
+
+impl AccessToken {
+    pub fn new(resp: &TokenResponse) -> Self {
+        AccessToken {
+            token: resp.access_token.clone(),
+            expires: Instant::now() + Duration::from_secs(resp.expires_in),
+        }
+    }
+
+    pub fn is_valid(&self) -> bool {
+        Instant::now() + EXPIRY_MARGIN < self.expires
+    }
+}
+
+/// Credentials to store in the config after a successful login
+pub struct Login {
+    pub client_id: String,
+    pub refresh_token: String,
+    pub access_token: AccessToken,
+}
+
+pub fn authorize_url(hass_address: &str, client_id: &str, redirect_uri: &str, state: &str) -> Url {
+    Url::parse_with_params(
+        &format!("{}/auth/authorize", hass_address),
+        &[
+            ("response_type", "code"),
+            ("client_id", client_id),
+            ("redirect_uri", redirect_uri),
+            ("state", state),
+        ],
+    )
+    .unwrap()
+}
+
+/// `code` and `state` of the redirect, from its HTTP request line
+pub fn parse_callback(request_line: &str) -> Option<(String, String)> {
+    let path = request_line.strip_prefix("GET ")?.split(' ').next()?;
+    let url = Url::parse(&format!("http://localhost{}", path)).ok()?;
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    Some((param("code")?, param("state")?))
+}
+
+/// Random value tying the redirect to this login attempt
+fn random_state() -> io::Result<String> {
+    let mut bytes = [0u8; 16];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Wait for the browser to be redirected back, and answer it
+async fn wait_for_code(listener: &TcpListener, state: &str) -> Result<String, LoginError> {
+    loop {
+        let (mut stream, _) = listener.accept().await?;
+        let mut request = vec![0u8; 4096];
+        let len = stream.read(&mut request).await?;
+        let request = String::from_utf8_lossy(&request[..len]);
+        let Some((code, received_state)) = parse_callback(request.lines().next().unwrap_or(""))
+        else {
+            // Favicon and such
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n")
+                .await?;
+            continue;
+        };
+        let body = "Logged in, you can close this page and go back to the terminal.";
+        stream
+            .write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+                .as_bytes(),
+            )
+            .await?;
+        if received_state != state {
+            return Err(LoginError::Denied(String::from("state mismatch")));
+        }
+        return Ok(code);
+    }
+}
+
+async fn request_token(
+    client: &reqwest::Client,
+    hass_address: &str,
+    params: &[(&str, &str)],
+) -> Result<TokenResponse, reqwest::Error> {
+    client
+        .post(format!("{}/auth/token", hass_address))
+        .form(params)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+}
+
+/// Run the whole login flow in the user's browser
+pub async fn login(client: &reqwest::Client, hass_address: &str) -> Result<Login, LoginError> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let port = listener.local_addr()?.port();
+    // Home Assistant accepts redirects to the same host and port as the client id
+    let client_id = format!("http://127.0.0.1:{}/", port);
+    let redirect_uri = format!("{}callback", client_id);
+    let state = random_state()?;
+    let url = authorize_url(hass_address, &client_id, &redirect_uri, &state);
+
+    println!("Log in to Home Assistant from your browser: {}", url);
+    let _ = Command::new("xdg-open")
+        .arg(url.as_str())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn();
+
+    let code = wait_for_code(&listener, &state).await?;
+    let resp = request_token(
+        client,
+        hass_address,
+        &[
+            ("grant_type", "authorization_code"),
+            ("code", &code),
+            ("client_id", &client_id),
+        ],
+    )
+    .await?;
+    let refresh_token = resp
+        .refresh_token
+        .clone()
+        .ok_or_else(|| LoginError::Denied(String::from("no refresh token")))?;
+    Ok(Login {
+        client_id,
+        refresh_token,
+        access_token: AccessToken::new(&resp),
+    })
+}
+
+/// New access token for a refresh token
+pub async fn refresh(
+    client: &reqwest::Client,
+    hass_address: &str,
+    client_id: &str,
+    refresh_token: &str,
+) -> Result<AccessToken, reqwest::Error> {
+    let resp = request_token(
+        client,
+        hass_address,
+        &[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", client_id),
+        ],
+    )
+    .await?;
+    Ok(AccessToken::new(&resp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{authorize_url, parse_callback};
+
+    #[test]
+    fn authorize_url_test() {
+        let url = authorize_url(
+            "http://hass.local:8123",
+            "http://127.0.0.1:4242/",
+            "http://127.0.0.1:4242/callback",
+            "abcd",
+        );
+        assert_eq!(
+            url.as_str(),
+            "http://hass.local:8123/auth/authorize?response_type=code&client_id=http%3A%2F%2F127.0.0.1%3A4242%2F&redirect_uri=http%3A%2F%2F127.0.0.1%3A4242%2Fcallback&state=abcd"
+        );
+    }
+
+    #[test]
+    fn parse_callback_test() {
+        assert_eq!(
+            parse_callback("GET /callback?code=12%2F34&state=abcd HTTP/1.1"),
+            Some((String::from("12/34"), String::from("abcd")))
+        );
+        assert_eq!(parse_callback("GET /favicon.ico HTTP/1.1"), None);
+    }
+}
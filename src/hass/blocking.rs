@@ -0,0 +1,137 @@
+//! Blocking wrapper around the async [`Client`](super::Client), for simple
+//! embedders that don't want to run a tokio runtime themselves.
+
+use super::{HassConfig, LocationData, Template, Zone};
+use crate::{
+    sensors::{DynSensor, DynSensorData, Provider},
+    Config,
+};
+use serde_json::Value;
+use std::{collections::BTreeMap, result};
+use tokio::runtime::{self, Runtime};
+
+pub struct Client {
+    inner: super::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    pub fn new(config: Config) -> result::Result<Self, reqwest::Error> {
+        let runtime = runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        // reqwest needs a runtime in the context to build its async client
+        let inner = {
+            let _guard = runtime.enter();
+            super::Client::new(config)?
+        };
+        Ok(Self { inner, runtime })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.inner.config
+    }
+
+    pub fn config_mut(&mut self) -> &mut Config {
+        &mut self.inner.config
+    }
+
+    pub fn set_login(&mut self, login: super::auth::Login) {
+        self.runtime.block_on(self.inner.set_login(login))
+    }
+
+    pub fn access_token(&self) -> result::Result<String, super::Error> {
+        self.runtime.block_on(self.inner.access_token())
+    }
+
+    pub fn register_app(&mut self) -> result::Result<(), super::Error> {
+        self.runtime.block_on(self.inner.register_app())
+    }
+
+    pub fn unregister_app(&mut self) -> result::Result<(), super::Error> {
+        self.runtime.block_on(self.inner.unregister_app())
+    }
+
+    pub fn check_api(&self) -> result::Result<String, super::Error> {
+        self.runtime.block_on(self.inner.check_api())
+    }
+
+    pub fn register_sensor(&self, sensor: &dyn DynSensor) -> result::Result<(), reqwest::Error> {
+        self.runtime.block_on(self.inner.register_sensor(sensor))
+    }
+
+    pub fn update_sensor(&self, sensor: &dyn DynSensor) -> result::Result<(), reqwest::Error> {
+        self.runtime.block_on(self.inner.update_sensor(sensor))
+    }
+
+    pub fn send_update(&self, update: &DynSensorData) -> result::Result<(), reqwest::Error> {
+        self.runtime.block_on(self.inner.send_update(update))
+    }
+
+    pub fn fire_event(
+        &self,
+        event_type: &str,
+        event_data: Value,
+    ) -> result::Result<(), reqwest::Error> {
+        self.runtime
+            .block_on(self.inner.fire_event(event_type, event_data))
+    }
+
+    pub fn render_templates(
+        &self,
+        templates: BTreeMap<String, Template>,
+    ) -> result::Result<BTreeMap<String, Value>, reqwest::Error> {
+        self.runtime
+            .block_on(self.inner.render_templates(templates))
+    }
+
+    pub fn render_template(
+        &self,
+        template: &str,
+        variables: Option<Value>,
+    ) -> result::Result<Value, reqwest::Error> {
+        self.runtime
+            .block_on(self.inner.render_template(template, variables))
+    }
+
+    pub fn update_location(&self, location: &LocationData) -> result::Result<(), reqwest::Error> {
+        self.runtime.block_on(self.inner.update_location(location))
+    }
+
+    pub fn update_registration(&self) -> result::Result<(), reqwest::Error> {
+        self.runtime.block_on(self.inner.update_registration())
+    }
+
+    pub fn get_config(&self) -> result::Result<HassConfig, reqwest::Error> {
+        self.runtime.block_on(self.inner.get_config())
+    }
+
+    pub fn get_zones(&self) -> result::Result<Vec<Zone>, reqwest::Error> {
+        self.runtime.block_on(self.inner.get_zones())
+    }
+
+    pub fn register_providers(
+        &self,
+        providers: &[Box<dyn Provider>],
+    ) -> result::Result<(), reqwest::Error> {
+        self.runtime
+            .block_on(self.inner.register_providers(providers))
+    }
+
+    pub fn update_providers(
+        &self,
+        providers: &[Box<dyn Provider>],
+    ) -> result::Result<(), reqwest::Error> {
+        self.runtime
+            .block_on(self.inner.update_providers(providers))
+    }
+
+    pub fn mark_unavailable(
+        &self,
+        providers: &[Box<dyn Provider>],
+    ) -> result::Result<(), reqwest::Error> {
+        self.runtime
+            .block_on(self.inner.mark_unavailable(providers))
+    }
+}
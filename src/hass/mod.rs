@@ -0,0 +1,699 @@
+use crate::{
+    sensors::{DynSensor, DynSensorData, Provider},
+    Config,
+};
+use futures::future;
+use reqwest;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt, result, sync::Arc, time::Duration};
+use tokio::sync::Mutex;
+
+pub mod auth;
+pub mod blocking;
+pub mod websocket;
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub client: reqwest::Client,
+    pub config: Config,
+    /// Access token of the login flow, refreshed when it expires
+    access_token: Arc<Mutex<Option<auth::AccessToken>>>,
+}
+
+/// Error of the requests made with a token
+#[derive(Debug)]
+pub enum Error {
+    Http(reqwest::Error),
+    Websocket(Box<websocket::ChannelError>),
+    /// Neither `auth_token` nor a login is set in the config
+    MissingCredentials,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "{}", err),
+            Error::Websocket(err) => write!(f, "{}", err),
+            Error::MissingCredentials => write!(f, "no auth_token or login in the config"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+#[derive(Serialize)]
+pub struct RegistrationInfoReq {
+    pub device_id: String,
+    pub app_id: String,
+    pub app_name: String,
+    pub app_version: String,
+    pub device_name: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub os_name: String,
+    pub os_version: String,
+    pub supports_encryption: bool,
+    pub app_data: AppData,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AppData {
+    /// Have notifications delivered through the websocket API
+    pub push_websocket_channel: bool,
+}
+
+#[derive(Deserialize)]
+pub struct RegistrationInfoResp {
+    pub secret: Option<String>,
+    pub webhook_id: Option<String>,
+}
+
+/// Webhook requests other than the sensor ones
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+#[serde(tag = "type")]
+pub enum WebhookData {
+    FireEvent { data: FireEventData },
+    RenderTemplate { data: BTreeMap<String, Template> },
+    GetConfig,
+    GetZones,
+    UpdateLocation { data: LocationData },
+    UpdateRegistration { data: RegistrationUpdate },
+}
+
+/// Registration values that can change without registering again
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RegistrationUpdate {
+    pub app_version: String,
+    pub device_name: String,
+    pub manufacturer: String,
+    pub model: String,
+    pub os_version: String,
+    pub app_data: AppData,
+}
+
+#[derive(Serialize)]
+pub struct FireEventData {
+    pub event_type: String,
+    pub event_data: Value,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LocationData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gps: Option<[f64; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gps_accuracy: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub altitude: Option<f64>,
+    /// Zone name, for devices without gps
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub location_name: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Template {
+    pub template: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variables: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct ApiStatus {
+    message: String,
+}
+
+/// Entry of an integration, as listed by `/api/config/config_entries/entry`
+#[derive(Deserialize)]
+struct ConfigEntry {
+    entry_id: String,
+}
+
+/// Home Assistant configuration, as returned by `get_config`
+#[derive(Deserialize, Debug, Clone)]
+pub struct HassConfig {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub elevation: f64,
+    pub unit_system: UnitSystem,
+    pub location_name: String,
+    pub time_zone: String,
+    pub components: Vec<String>,
+    pub version: String,
+}
+
+impl HassConfig {
+    pub fn has_component(&self, component: &str) -> bool {
+        self.components.iter().any(|c| c == component)
+    }
+}
+
+/// Zone entity, as returned by `get_zones`
+#[derive(Deserialize, Debug, Clone)]
+pub struct Zone {
+    pub entity_id: String,
+    pub attributes: ZoneAttributes,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ZoneAttributes {
+    pub friendly_name: Option<String>,
+    pub latitude: f64,
+    pub longitude: f64,
+    pub radius: f64,
+}
+
+impl Zone {
+    /// Name to send as `location_name` to be in this zone
+    pub fn location_name(&self) -> String {
+        if self.entity_id == "zone.home" {
+            return String::from("home");
+        }
+        self.attributes
+            .friendly_name
+            .clone()
+            .unwrap_or_else(|| self.entity_id.trim_start_matches("zone.").to_string())
+    }
+
+    /// Whether `name` designates this zone, by entity id or friendly name
+    pub fn is_named(&self, name: &str) -> bool {
+        self.entity_id.eq_ignore_ascii_case(name)
+            || self
+                .attributes
+                .friendly_name
+                .as_ref()
+                .is_some_and(|friendly_name| friendly_name.eq_ignore_ascii_case(name))
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct UnitSystem {
+    pub length: String,
+    pub mass: String,
+    /// `°C` or `°F`
+    pub temperature: String,
+    pub volume: String,
+}
+
+impl Client {
+    pub fn new(config: Config) -> result::Result<Self, reqwest::Error> {
+        Ok(Self {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(config.request_timeout))
+                .build()?,
+            config,
+            access_token: Default::default(),
+        })
+    }
+
+    /// Use the tokens of a successful login flow from now on
+    pub async fn set_login(&mut self, login: auth::Login) {
+        self.config.auth_token = None;
+        self.config.refresh_token = Some(login.refresh_token);
+        self.config.oauth_client_id = Some(login.client_id);
+        *self.access_token.lock().await = Some(login.access_token);
+    }
+
+    /// Token for the authenticated APIs: the long-lived `auth_token`, or an
+    /// access token obtained from the refresh token of the login flow
+    pub async fn access_token(&self) -> result::Result<String, Error> {
+        let (Some(refresh_token), Some(client_id)) =
+            (&self.config.refresh_token, &self.config.oauth_client_id)
+        else {
+            return self
+                .config
+                .auth_token
+                .clone()
+                .ok_or(Error::MissingCredentials);
+        };
+        let mut access_token = self.access_token.lock().await;
+        match access_token.as_ref() {
+            Some(token) if token.is_valid() => Ok(token.token.clone()),
+            _ => {
+                let token = auth::refresh(
+                    &self.client,
+                    self.config.hass_address.as_ref().unwrap(),
+                    client_id,
+                    refresh_token,
+                )
+                .await?;
+                *access_token = Some(token.clone());
+                Ok(token.token)
+            }
+        }
+    }
+
+    pub async fn register_app(&mut self) -> result::Result<(), Error> {
+        let resp = self
+            .client
+            .post(format!(
+                "{}/api/mobile_app/registrations",
+                self.config.hass_address.as_ref().unwrap()
+            ))
+            .bearer_auth(self.access_token().await?)
+            .json(&RegistrationInfoReq {
+                device_id: self.config.device_id.clone(),
+                app_id: self.config.app_id.clone(),
+                app_name: self.config.app_name.clone(),
+                app_version: self.config.app_version.clone(),
+                device_name: self.config.device_name.clone(),
+                manufacturer: self.config.manufacturer.clone(),
+                model: self.config.model.clone(),
+                os_name: self.config.os_name.clone(),
+                os_version: self.config.os_version.clone(),
+                supports_encryption: self.config.support_encryption,
+                app_data: AppData {
+                    push_websocket_channel: true,
+                },
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+        let resp_body: RegistrationInfoResp = resp.json().await?;
+        self.config.webhook_id = resp_body.webhook_id;
+        self.config.webhook_secret = resp_body.secret;
+        Ok(())
+    }
+
+    /// Remove the `mobile_app` entry of this device from Home Assistant,
+    /// which needs a token of an admin user
+    pub async fn unregister_app(&mut self) -> result::Result<(), Error> {
+        let address = self.config.hass_address.as_ref().unwrap();
+        let token = self.access_token().await?;
+        let entries: Vec<ConfigEntry> = self
+            .client
+            .get(format!("{}/api/config/config_entries/entry", address))
+            .bearer_auth(&token)
+            .query(&[("domain", "mobile_app")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // Only the entries of this device, others may have the same name
+        let device_entries = self
+            .device_config_entries()
+            .await
+            .map_err(|err| Error::Websocket(Box::new(err)))?;
+        for entry in entries
+            .iter()
+            .filter(|entry| device_entries.contains(&entry.entry_id))
+        {
+            self.client
+                .delete(format!(
+                    "{}/api/config/config_entries/entry/{}",
+                    address, entry.entry_id
+                ))
+                .bearer_auth(&token)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        self.config.webhook_id = None;
+        self.config.webhook_secret = None;
+        Ok(())
+    }
+
+    /// Check the address and token against the REST API, returning its message
+    pub async fn check_api(&self) -> result::Result<String, Error> {
+        let status: ApiStatus = self
+            .client
+            .get(format!(
+                "{}/api/",
+                self.config.hass_address.as_ref().unwrap()
+            ))
+            .bearer_auth(self.access_token().await?)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(status.message)
+    }
+
+    pub async fn register_sensor(
+        &self,
+        sensor: &dyn DynSensor,
+    ) -> result::Result<(), reqwest::Error> {
+        self.client
+            .post(self.webhook_url())
+            .json(&sensor.register_info())
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn update_sensor(
+        &self,
+        sensor: &dyn DynSensor,
+    ) -> result::Result<(), reqwest::Error> {
+        self.send_update(&sensor.update_info()).await
+    }
+
+    /// Send an already built sensor update, as produced by the scheduler
+    pub async fn send_update(&self, update: &DynSensorData) -> result::Result<(), reqwest::Error> {
+        self.client
+            .post(self.webhook_url())
+            .json(update)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Send several updates concurrently
+    pub async fn send_updates(
+        &self,
+        updates: &[DynSensorData],
+    ) -> result::Result<(), reqwest::Error> {
+        future::join_all(updates.iter().map(|update| self.send_update(update)))
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    /// Fire an event on the Home Assistant event bus
+    pub async fn fire_event(
+        &self,
+        event_type: &str,
+        event_data: Value,
+    ) -> result::Result<(), reqwest::Error> {
+        self.client
+            .post(self.webhook_url())
+            .json(&WebhookData::FireEvent {
+                data: FireEventData {
+                    event_type: String::from(event_type),
+                    event_data,
+                },
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Render several templates at once, by name
+    pub async fn render_templates(
+        &self,
+        templates: BTreeMap<String, Template>,
+    ) -> result::Result<BTreeMap<String, Value>, reqwest::Error> {
+        self.client
+            .post(self.webhook_url())
+            .json(&WebhookData::RenderTemplate { data: templates })
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// Render a single template
+    pub async fn render_template(
+        &self,
+        template: &str,
+        variables: Option<Value>,
+    ) -> result::Result<Value, reqwest::Error> {
+        let mut templates = BTreeMap::new();
+        templates.insert(
+            String::from("template"),
+            Template {
+                template: String::from(template),
+                variables,
+            },
+        );
+        let mut rendered = self.render_templates(templates).await?;
+        Ok(rendered.remove("template").unwrap_or(Value::Null))
+    }
+
+    /// Update the device tracker of the laptop
+    pub async fn update_location(
+        &self,
+        location: &LocationData,
+    ) -> result::Result<(), reqwest::Error> {
+        self.client
+            .post(self.webhook_url())
+            .json(&WebhookData::UpdateLocation {
+                data: location.clone(),
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Send the current device description of the config to Home Assistant
+    pub async fn update_registration(&self) -> result::Result<(), reqwest::Error> {
+        self.client
+            .post(self.webhook_url())
+            .json(&WebhookData::UpdateRegistration {
+                data: RegistrationUpdate {
+                    app_version: self.config.app_version.clone(),
+                    device_name: self.config.device_name.clone(),
+                    manufacturer: self.config.manufacturer.clone(),
+                    model: self.config.model.clone(),
+                    os_version: self.config.os_version.clone(),
+                    app_data: AppData {
+                        push_websocket_channel: true,
+                    },
+                },
+            })
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn get_config(&self) -> result::Result<HassConfig, reqwest::Error> {
+        self.client
+            .post(self.webhook_url())
+            .json(&WebhookData::GetConfig)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    pub async fn get_zones(&self) -> result::Result<Vec<Zone>, reqwest::Error> {
+        self.client
+            .post(self.webhook_url())
+            .json(&WebhookData::GetZones)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// Register every sensor of every provider
+    pub async fn register_providers(
+        &self,
+        providers: &[Box<dyn Provider>],
+    ) -> result::Result<(), reqwest::Error> {
+        for provider in providers {
+            for sensor in provider.sensors() {
+                self.register_sensor(sensor).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Send the current state of every sensor of every provider
+    pub async fn update_providers(
+        &self,
+        providers: &[Box<dyn Provider>],
+    ) -> result::Result<(), reqwest::Error> {
+        let updates: Vec<DynSensorData> = providers
+            .iter()
+            .flat_map(|provider| {
+                provider.sensors().into_iter().map(|sensor| {
+                    if provider.available() {
+                        sensor.update_info()
+                    } else {
+                        sensor.unavailable_info()
+                    }
+                })
+            })
+            .collect();
+        self.send_updates(&updates).await
+    }
+
+    /// Mark every sensor of every provider as unavailable
+    pub async fn mark_unavailable(
+        &self,
+        providers: &[Box<dyn Provider>],
+    ) -> result::Result<(), reqwest::Error> {
+        let updates: Vec<DynSensorData> = providers
+            .iter()
+            .flat_map(|provider| provider.sensors())
+            .map(|sensor| sensor.unavailable_info())
+            .collect();
+        self.send_updates(&updates).await
+    }
+
+    fn webhook_url(&self) -> String {
+        format!(
+            "{}/api/webhook/{}",
+            self.config.hass_address.as_ref().unwrap(),
+            self.config.webhook_id.as_ref().unwrap()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AppData, FireEventData, HassConfig, RegistrationUpdate, Template, WebhookData};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    use crate::sensors::{battery::BatteryProvider, DynSensor, Provider};
+
+    use crate::Config;
+
+    use super::{Client, Error};
+
+    #[tokio::test]
+    async fn missing_credentials_test() {
+        let config = Config {
+            hass_address: Some(String::from("http://hass.local:8123")),
+            ..Default::default()
+        };
+        let client = Client::new(config).unwrap();
+        assert!(matches!(
+            client.access_token().await,
+            Err(Error::MissingCredentials)
+        ));
+    }
+
+    #[tokio::test]
+    async fn register_test() {
+        match Client::new(Config::new()) {
+            Ok(mut client) => {
+                client.register_app().await.unwrap();
+                println!(
+                    "serialized = {}",
+                    client.config.webhook_id.as_ref().unwrap()
+                );
+                client.config.save_to_file().unwrap();
+            }
+            Err(_) => panic!("prout"),
+        }
+    }
+    #[tokio::test]
+    async fn register_sensor() {
+        match Client::new(Config::new()) {
+            Ok(client) => {
+                let batt_provider = BatteryProvider::new();
+                client.register_sensor(&batt_provider.level).await.unwrap();
+            }
+            Err(_) => panic!("prout"),
+        }
+    }
+    #[tokio::test]
+    async fn update_sensor() {
+        match Client::new(Config::new()) {
+            Ok(client) => {
+                let mut batt_provider = BatteryProvider::new();
+                batt_provider.update_all().unwrap();
+                println!(
+                    "serialized = {}",
+                    serde_json::to_string(&batt_provider.level.update_info()).unwrap()
+                );
+                client.update_sensor(&batt_provider.level).await.unwrap();
+            }
+            Err(_) => panic!("prout"),
+        }
+    }
+    #[test]
+    fn fire_event_test() {
+        let req = WebhookData::FireEvent {
+            data: FireEventData {
+                event_type: String::from("build_finished"),
+                event_data: json!({"success": true}),
+            },
+        };
+        assert_eq!(
+            serde_json::to_string(&req).unwrap(),
+            r#"{"type":"fire_event","data":{"event_type":"build_finished","event_data":{"success":true}}}"#
+        );
+    }
+    #[test]
+    fn update_registration_test() {
+        let req = WebhookData::UpdateRegistration {
+            data: RegistrationUpdate {
+                app_version: String::from("0.1.1"),
+                device_name: String::from("laptop"),
+                manufacturer: String::from("LENOVO"),
+                model: String::from("ThinkPad X1 Carbon Gen 9"),
+                os_version: String::from("40"),
+                app_data: AppData {
+                    push_websocket_channel: true,
+                },
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&req).unwrap(),
+            json!({
+                "type": "update_registration",
+                "data": {
+                    "app_version": "0.1.1",
+                    "device_name": "laptop",
+                    "manufacturer": "LENOVO",
+                    "model": "ThinkPad X1 Carbon Gen 9",
+                    "os_version": "40",
+                    "app_data": {"push_websocket_channel": true}
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn render_template_test() {
+        let mut templates = BTreeMap::new();
+        templates.insert(
+            String::from("greeting"),
+            Template {
+                template: String::from("Hello {{ name }}"),
+                variables: Some(json!({"name": "Paulus"})),
+            },
+        );
+        assert_eq!(
+            serde_json::to_string(&WebhookData::RenderTemplate { data: templates }).unwrap(),
+            r#"{"type":"render_template","data":{"greeting":{"template":"Hello {{ name }}","variables":{"name":"Paulus"}}}}"#
+        );
+    }
+    #[test]
+    fn get_config_test() {
+        assert_eq!(
+            serde_json::to_string(&WebhookData::GetConfig).unwrap(),
+            r#"{"type":"get_config"}"#
+        );
+        let config: HassConfig = serde_json::from_value(json!({
+            "latitude": 32.87336,
+            "longitude": -117.22743,
+            "elevation": 430,
+            "unit_system": {
+                "length": "mi",
+                "mass": "lb",
+                "temperature": "°F",
+                "volume": "gal"
+            },
+            "location_name": "Home",
+            "time_zone": "America/Los_Angeles",
+            "components": ["frontend", "mobile_app"],
+            "version": "2024.5.0",
+            "theme_color": "#03A9F4"
+        }))
+        .unwrap();
+        assert!(config.has_component("mobile_app"));
+        assert_eq!(config.unit_system.temperature, "°F");
+    }
+}
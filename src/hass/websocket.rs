@@ -0,0 +1,258 @@
+//! Home Assistant websocket API, used to receive the push notifications sent to
+//! this device through `mobile_app/push_notification_channel`.
+
+use super::Client;
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use tokio::net::TcpStream;
+use tokio_tungstenite::{
+    connect_async,
+    tungstenite::{self, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+
+#[derive(Debug)]
+pub enum ChannelError {
+    Websocket(tungstenite::Error),
+    Json(serde_json::Error),
+    /// Unable to refresh the access token
+    Token(super::Error),
+    /// Authentication or subscription refused by Home Assistant
+    Refused(String),
+    Closed,
+}
+
+impl fmt::Display for ChannelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelError::Websocket(err) => write!(f, "websocket error: {}", err),
+            ChannelError::Json(err) => write!(f, "invalid message: {}", err),
+            ChannelError::Token(err) => write!(f, "unable to get an access token: {}", err),
+            ChannelError::Refused(reason) => write!(f, "refused by Home Assistant: {}", reason),
+            ChannelError::Closed => write!(f, "connection closed"),
+        }
+    }
+}
+
+impl std::error::Error for ChannelError {}
+
+impl From<tungstenite::Error> for ChannelError {
+    fn from(err: tungstenite::Error) -> Self {
+        ChannelError::Websocket(err)
+    }
+}
+
+impl From<serde_json::Error> for ChannelError {
+    fn from(err: serde_json::Error) -> Self {
+        ChannelError::Json(err)
+    }
+}
+
+/// A message pushed by Home Assistant, through `notify.mobile_app_*`
+#[derive(Deserialize, Debug, Clone)]
+pub struct PushMessage {
+    pub message: String,
+    pub title: Option<String>,
+    #[serde(default)]
+    pub data: Map<String, Value>,
+    pub hass_confirm_id: Option<String>,
+}
+
+impl PushMessage {
+    /// Name of the command for `command_<name>` messages
+    pub fn command(&self) -> Option<&str> {
+        self.message.strip_prefix("command_")
+    }
+}
+
+pub struct PushChannel {
+    stream: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    webhook_id: String,
+    subscription_id: u64,
+    next_id: u64,
+}
+
+impl Client {
+    /// Open the websocket API and authenticate
+    async fn open_websocket(&self) -> Result<PushChannel, ChannelError> {
+        let url = format!(
+            "{}/api/websocket",
+            self.config
+                .hass_address
+                .as_ref()
+                .unwrap()
+                .replacen("http", "ws", 1)
+        );
+        let (stream, _) = connect_async(url).await?;
+        let mut channel = PushChannel {
+            stream,
+            webhook_id: self.config.webhook_id.clone().unwrap_or_default(),
+            subscription_id: 1,
+            next_id: 2,
+        };
+        let token = self.access_token().await.map_err(ChannelError::Token)?;
+        channel.authenticate(&token).await?;
+        Ok(channel)
+    }
+
+    /// Open the websocket API and subscribe to the push notification channel
+    pub async fn push_notification_channel(&self) -> Result<PushChannel, ChannelError> {
+        let mut channel = self.open_websocket().await?;
+        channel.subscribe().await?;
+        Ok(channel)
+    }
+
+    /// Config entries of the devices registered with this `device_id`,
+    /// from the device registry
+    pub async fn device_config_entries(&self) -> Result<Vec<String>, ChannelError> {
+        let mut channel = self.open_websocket().await?;
+        let devices = channel
+            .call(json!({"type": "config/device_registry/list"}))
+            .await?;
+        Ok(device_entries(&devices, &self.config.device_id))
+    }
+}
+
+/// Config entries of the `mobile_app` devices with this id, in the result of
+/// `config/device_registry/list`
+fn device_entries(devices: &Value, device_id: &str) -> Vec<String> {
+    let identifier = json!(["mobile_app", device_id]);
+    devices
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|device| {
+            device["identifiers"]
+                .as_array()
+                .is_some_and(|identifiers| identifiers.contains(&identifier))
+        })
+        .filter_map(|device| device["config_entries"].as_array())
+        .flatten()
+        .filter_map(|entry| entry.as_str().map(String::from))
+        .collect()
+}
+
+impl PushChannel {
+    async fn send(&mut self, message: Value) -> Result<(), ChannelError> {
+        self.stream.send(Message::text(message.to_string())).await?;
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<Value, ChannelError> {
+        loop {
+            match self.stream.next().await {
+                Some(Ok(Message::Text(text))) => return Ok(serde_json::from_str(&text)?),
+                Some(Ok(Message::Close(_))) | None => return Err(ChannelError::Closed),
+                Some(Ok(_)) => (),
+                Some(Err(err)) => return Err(err.into()),
+            }
+        }
+    }
+
+    async fn authenticate(&mut self, token: &str) -> Result<(), ChannelError> {
+        // Home Assistant starts with `auth_required`
+        self.receive().await?;
+        self.send(json!({"type": "auth", "access_token": token}))
+            .await?;
+        let resp = self.receive().await?;
+        match resp["type"].as_str() {
+            Some("auth_ok") => Ok(()),
+            _ => Err(ChannelError::Refused(
+                resp["message"]
+                    .as_str()
+                    .unwrap_or("auth failed")
+                    .to_string(),
+            )),
+        }
+    }
+
+    async fn subscribe(&mut self) -> Result<(), ChannelError> {
+        self.send(json!({
+            "id": self.subscription_id,
+            "type": "mobile_app/push_notification_channel",
+            "webhook_id": self.webhook_id,
+            "support_confirm": true,
+        }))
+        .await?;
+        let resp = self.receive().await?;
+        if resp["success"].as_bool() == Some(true) {
+            Ok(())
+        } else {
+            Err(ChannelError::Refused(resp["error"]["message"].to_string()))
+        }
+    }
+
+    /// Send a command and wait for its result
+    async fn call(&mut self, mut command: Value) -> Result<Value, ChannelError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        command["id"] = id.into();
+        self.send(command).await?;
+        loop {
+            let mut resp = self.receive().await?;
+            if resp["type"] != "result" || resp["id"] != id {
+                continue;
+            }
+            if resp["success"].as_bool() != Some(true) {
+                return Err(ChannelError::Refused(resp["error"]["message"].to_string()));
+            }
+            return Ok(resp["result"].take());
+        }
+    }
+
+    /// Wait for the next pushed message, confirming its reception
+    pub async fn next(&mut self) -> Result<PushMessage, ChannelError> {
+        loop {
+            let resp = self.receive().await?;
+            if resp["type"] != "event" || resp["id"] != self.subscription_id {
+                continue;
+            }
+            let message: PushMessage = serde_json::from_value(resp["event"].clone())?;
+            if let Some(confirm_id) = &message.hass_confirm_id {
+                let id = self.next_id;
+                self.next_id += 1;
+                self.send(json!({
+                    "id": id,
+                    "type": "mobile_app/push_notification_confirm",
+                    "webhook_id": self.webhook_id,
+                    "confirm_id": confirm_id,
+                }))
+                .await?;
+            }
+            return Ok(message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{device_entries, PushMessage};
+    use serde_json::json;
+
+    #[test]
+    fn device_entries_test() {
+        let devices = json!([
+            {"name": "laptop", "identifiers": [["mobile_app", "c84a052f"]], "config_entries": ["01J0"]},
+            {"name": "laptop", "identifiers": [["mobile_app", "other"]], "config_entries": ["01J1"]},
+            {"name": "router", "identifiers": [["unifi", "c84a052f"]], "config_entries": ["01J2"]}
+        ]);
+        assert_eq!(
+            device_entries(&devices, "c84a052f"),
+            vec![String::from("01J0")]
+        );
+    }
+
+    #[test]
+    fn deserialize_test() {
+        let message: PushMessage = serde_json::from_str(
+            r#"{"message":"command_lock","title":"Lock","data":{"actions":[]},"hass_confirm_id":"42"}"#,
+        )
+        .unwrap();
+        assert_eq!(message.message, "command_lock");
+        assert_eq!(message.hass_confirm_id.as_deref(), Some("42"));
+        assert!(message.data.contains_key("actions"));
+        assert_eq!(message.command(), Some("lock"));
+    }
+}
@@ -0,0 +1,141 @@
+//! `init` command: interactive creation of the config file, from the Home
+//! Assistant address and login to the registration of the app.
+
+use rusty_hass_laptop::{
+    hass::{self, auth},
+    Config,
+};
+use std::{
+    io::{self, BufRead, Write},
+    path::Path,
+    process,
+};
+
+fn prompt(question: &str, default: Option<&str>) -> String {
+    match default {
+        Some(default) => print!("{} [{}]: ", question, default),
+        None => print!("{}: ", question),
+    }
+    io::stdout().flush().unwrap();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).unwrap_or(0) == 0 {
+        // stdin closed, nothing more will be answered
+        process::exit(1);
+    }
+    match answer.trim() {
+        "" => default.unwrap_or("").to_string(),
+        answer => answer.to_string(),
+    }
+}
+
+fn confirm(question: &str) -> bool {
+    matches!(
+        prompt(&format!("{} (y/N)", question), None).as_str(),
+        "y" | "Y" | "yes"
+    )
+}
+
+/// `http://host:8123` from what the user typed
+pub fn normalize_address(address: &str) -> String {
+    let address = address.trim().trim_end_matches('/');
+    let address = address.strip_suffix("/api").unwrap_or(address);
+    if address.contains("://") {
+        address.to_string()
+    } else {
+        format!("http://{}", address)
+    }
+}
+
+/// Whether Home Assistant answers on `/api/`, which needs a token, so a 401
+/// is expected here
+async fn is_hass(address: &str) -> bool {
+    match reqwest::get(format!("{}/api/", address)).await {
+        Ok(resp) => {
+            resp.status().is_success() || resp.status() == reqwest::StatusCode::UNAUTHORIZED
+        }
+        Err(err) => {
+            println!("Unable to reach {}: {}", address, err);
+            false
+        }
+    }
+}
+
+pub async fn run(path: &Path) {
+    if path.exists() && !confirm(&format!("{} already exists, overwrite it?", path.display())) {
+        return;
+    }
+    let mut config = Config::default();
+
+    loop {
+        let address = normalize_address(&prompt(
+            "Home Assistant address",
+            Some("http://homeassistant.local:8123"),
+        ));
+        if is_hass(&address).await {
+            config.hass_address = Some(address);
+            break;
+        }
+        println!("No Home Assistant API found there");
+    }
+
+    config.device_name = prompt("Device name", Some(&config.device_name));
+    config.manufacturer = prompt("Manufacturer", Some(&config.manufacturer));
+    config.model = prompt("Model", Some(&config.model));
+
+    let mut client = hass::Client::new(config).unwrap();
+    if confirm("Use a long-lived access token instead of logging in from the browser?") {
+        loop {
+            println!("Create a long-lived access token from your Home Assistant profile page");
+            let token = rpassword::prompt_password("Access token: ").unwrap_or_default();
+            client.config.auth_token = Some(token.trim().to_string());
+            match client.check_api().await {
+                Ok(_) => break,
+                Err(err) => println!("The token was refused: {}", err),
+            }
+        }
+    } else {
+        let address = client.config.hass_address.clone().unwrap();
+        match auth::login(&client.client, &address).await {
+            Ok(login) => client.set_login(login).await,
+            Err(err) => {
+                eprintln!("Unable to log in: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    if let Err(err) = client.register_app().await {
+        eprintln!("Unable to register the app: {}", err);
+        process::exit(1);
+    }
+    if client.config.webhook_id.is_none() {
+        eprintln!("Home Assistant refused the registration, is the mobile_app integration loaded?");
+        process::exit(1);
+    }
+    if let Err(err) = client.config.write_to(path) {
+        eprintln!("{}: {}", path.display(), err);
+        process::exit(1);
+    }
+    println!(
+        "Registered {}, config written to {}",
+        client.config.device_name,
+        path.display()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize_address;
+
+    #[test]
+    fn normalize_address_test() {
+        assert_eq!(
+            normalize_address("hass.local:8123"),
+            "http://hass.local:8123"
+        );
+        assert_eq!(
+            normalize_address(" https://hass.example.com/api/ "),
+            "https://hass.example.com"
+        );
+    }
+}
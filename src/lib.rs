@@ -1,23 +1,190 @@
+use commands::actions::Action;
 use convert_case::{Case, Casing};
 use directories::{self};
+use format::{ConfigFormat, CONFIG_FILE_NAMES};
+use hmac_sha256::Hash;
+use location::LocationConfig;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::{self, File},
-    io::{BufReader, Write},
-    ops::Not,
-    path::Path,
+    collections::BTreeMap,
+    env,
+    fs::{self, OpenOptions, Permissions},
+    io::Write,
+    os::unix::{
+        ffi::OsStrExt,
+        fs::{OpenOptionsExt, PermissionsExt},
+    },
+    path::{Path, PathBuf},
 };
-use sys_info;
 
+// Lets `#[derive(Sensor)]` refer to `::rusty_hass_laptop` from inside this crate.
+extern crate self as rusty_hass_laptop;
+
+pub mod commands;
+pub mod device;
+pub mod format;
 pub mod hass;
+pub mod location;
+pub mod logind;
+pub mod notify;
+pub mod os;
+pub mod scheduler;
 pub mod sensors;
+pub mod status;
+pub mod uevent;
+pub mod wifi;
+
+/// Environment variable overriding the config file path
+pub const CONFIG_ENV: &str = "RHL_CONFIG";
+/// Directory of the config file used when the user has none
+pub const SYSTEM_CONFIG_DIR: &str = "/etc/rusty-hass-laptop";
+/// Manufacturer and model when they can't be read
+const UNKNOWN: &str = "Unknown";
+/// `os_name` of the first versions, sent with the kernel version
+const LEGACY_OS_NAME: &str = "Linux";
+
+/// Runtime state of the app, kept apart from hand written TOML and YAML
+/// configs so they are never rewritten
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct State {
+    pub webhook_id: Option<String>,
+    pub webhook_secret: Option<String>,
+    /// Device description last sent to Home Assistant, so values migrated on
+    /// load are only pushed once
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub registered: Option<Registered>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Registered {
+    pub manufacturer: String,
+    pub model: String,
+    pub os_name: String,
+    pub os_version: String,
+}
+
+impl Registered {
+    fn from_config(config: &Config) -> Self {
+        Registered {
+            manufacturer: config.manufacturer.clone(),
+            model: config.model.clone(),
+            os_name: config.os_name.clone(),
+            os_version: config.os_version.clone(),
+        }
+    }
+}
+
+impl State {
+    /// One state file per config file, named after [`path_key`]
+    fn path(config_path: &Path) -> Option<PathBuf> {
+        directories::ProjectDirs::from("fr", "obito1903", env!("CARGO_PKG_NAME")).map(|dirs| {
+            dirs.state_dir()
+                .unwrap_or(dirs.data_local_dir())
+                .join(format!("state-{}.json", path_key(config_path)))
+        })
+    }
+
+    pub fn load(config_path: &Path) -> Option<Self> {
+        State::read(&State::path(config_path)?)
+    }
+
+    fn read(path: &Path) -> Option<Self> {
+        serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+    }
+
+    pub fn save(&self, config_path: &Path) -> Result<(), &'static str> {
+        self.save_to(&State::path(config_path).ok_or("No state directory")?)
+    }
+
+    fn save_to(&self, path: &Path) -> Result<(), &'static str> {
+        write_atomic(path, &serde_json::to_string_pretty(self).unwrap())
+    }
+
+    fn from_config(config: &Config) -> Self {
+        State {
+            webhook_id: config.webhook_id.clone(),
+            webhook_secret: config.webhook_secret.clone(),
+            registered: Some(Registered::from_config(config)),
+        }
+    }
+
+    /// Fill the registration of a parsed config, unless the file has its own
+    fn apply(self, value: &mut serde_json::Value) {
+        let Some(config) = value.as_object_mut() else {
+            return;
+        };
+        if config.get("webhook_id").is_none_or(|id| id.is_null()) {
+            config.insert(
+                String::from("webhook_id"),
+                serde_json::json!(self.webhook_id),
+            );
+            config.insert(
+                String::from("webhook_secret"),
+                serde_json::json!(self.webhook_secret),
+            );
+        }
+    }
+}
+
+/// Short hash of the canonical path of a config file, naming the files kept
+/// for it outside of the config directory
+fn path_key(path: &Path) -> String {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    Hash::hash(path.as_os_str().as_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Write `content` to `path` through a temporary file renamed over it, so an
+/// interrupted write never leaves a truncated file behind. The files hold
+/// tokens, so they are only readable by their owner, and only their mode is
+/// fixed when their content didn't change.
+fn write_atomic(path: &Path, content: &str) -> Result<(), &'static str> {
+    if fs::read_to_string(path).is_ok_and(|current| current == content) {
+        let mode = fs::metadata(path).map_or(0, |metadata| metadata.permissions().mode());
+        if mode & 0o777 == 0o600 {
+            return Ok(());
+        }
+        return fs::set_permissions(path, Permissions::from_mode(0o600))
+            .map_err(|_| "Unable to set the permissions of the file");
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|_| "Unable to create the directory")?;
+    }
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(&tmp_path)
+        .map_err(|_| "Unable to write the file")?;
+    // A leftover temporary file keeps its previous mode
+    file.set_permissions(Permissions::from_mode(0o600))
+        .and_then(|_| file.write_all(content.as_bytes()))
+        .and_then(|_| file.sync_all())
+        .map_err(|_| "Unable to write the file")?;
+    fs::rename(&tmp_path, path).map_err(|_| "Unable to write the file")
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(default)]
 pub struct Config {
     #[serde(skip)]
     pub config_file: Option<String>,
+    /// Set when values sent at registration were migrated on load, so they
+    /// are pushed with `update_registration`
+    #[serde(skip)]
+    pub registration_changed: bool,
     pub auth_token: Option<String>,
+    /// Refresh token of the login flow, used instead of `auth_token`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    /// Client id the refresh token was issued to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub oauth_client_id: Option<String>,
     pub webhook_id: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub webhook_secret: Option<String>,
@@ -32,21 +199,27 @@ pub struct Config {
     pub os_name: String,
     pub os_version: String,
     pub support_encryption: bool,
+    /// Timeout in seconds of each request made to Home Assistant
+    pub request_timeout: u64,
+    /// Actions Home Assistant is allowed to trigger with `command_<name>`
+    pub actions: BTreeMap<String, Action>,
+    /// Device tracker settings
+    pub location: LocationConfig,
 }
 
 impl Default for Config {
     fn default() -> Self {
+        let os_release = os::os_release();
         Config {
             config_file: None,
+            registration_changed: false,
             auth_token: None,
+            refresh_token: None,
+            oauth_client_id: None,
             webhook_id: None,
             webhook_secret: None,
             hass_address: None,
-            device_id: format!(
-                "{}_{}",
-                sys_info::hostname().unwrap(),
-                sys_info::os_type().unwrap()
-            ),
+            device_id: device::device_id().unwrap_or_else(device::legacy_device_id),
             app_id: env!("CARGO_PKG_NAME").parse().unwrap(),
             app_name: env!("CARGO_PKG_NAME")
                 .parse()
@@ -54,41 +227,186 @@ impl Default for Config {
                 .to_case(Case::Title),
             app_version: env!("CARGO_PKG_VERSION").parse().unwrap(),
             device_name: sys_info::hostname().unwrap(),
-            manufacturer: String::from("Unknown"),
-            model: String::from("Unknown"),
-            os_name: sys_info::os_type().unwrap(),
-            os_version: sys_info::os_release().unwrap(),
+            manufacturer: device::manufacturer().unwrap_or(String::from(UNKNOWN)),
+            model: device::model().unwrap_or(String::from(UNKNOWN)),
+            os_name: os_release
+                .as_ref()
+                .map_or_else(|| sys_info::os_type().unwrap(), |os| os.name.clone()),
+            os_version: os_release
+                .and_then(|os| os.version)
+                .unwrap_or_else(|| sys_info::os_release().unwrap()),
             support_encryption: false,
+            request_timeout: 10,
+            actions: BTreeMap::new(),
+            location: LocationConfig::default(),
         }
     }
 }
 
 impl Config {
     pub fn new() -> Self {
-        let project_dir = directories::ProjectDirs::from("fr", "obito1903", env!("CARGO_PKG_NAME"));
-        if project_dir.is_none() {
-            Config::default()
-        } else {
-            let config_path = project_dir
-                .as_ref()
-                .unwrap()
-                .config_dir()
-                .join("config.json");
-            if config_path.exists() {
-                Config::load_from_file(config_path.to_str().unwrap().parse().unwrap()).unwrap()
-            } else {
-                let config = Config::default();
-                config.save_to_file().unwrap();
-                config
+        Config::load(None).unwrap()
+    }
+
+    /// Load the config file from [`Config::resolve_path`], creating a default
+    /// one if needed. A file asked for explicitly has to exist.
+    pub fn load(flag: Option<PathBuf>) -> Result<Self, &'static str> {
+        let requested = Config::requested_path(flag.clone()).is_some();
+        match Config::resolve_path(flag) {
+            None => Ok(Config::default()),
+            Some(path) if path.exists() || requested => {
+                Config::load_from_file(path.to_string_lossy().into_owned())
+            }
+            Some(path) => {
+                let config = Config {
+                    config_file: Some(path.to_string_lossy().into_owned()),
+                    ..Default::default()
+                };
+                config.save_to_file()?;
+                Ok(config)
+            }
+        }
+    }
+
+    /// Path asked for with `--config`, or else with `$RHL_CONFIG`
+    pub fn requested_path(flag: Option<PathBuf>) -> Option<PathBuf> {
+        flag.or_else(|| env::var_os(CONFIG_ENV).map(PathBuf::from))
+    }
+
+    /// Config file to use: the requested one, then the first existing of the
+    /// user and system-wide ones, else the user one, to be created
+    pub fn resolve_path(flag: Option<PathBuf>) -> Option<PathBuf> {
+        let find_in = |dir: &Path| {
+            CONFIG_FILE_NAMES
+                .iter()
+                .map(|name| dir.join(name))
+                .find(|path| path.exists())
+        };
+        Config::requested_path(flag).or_else(|| {
+            let user = Config::default_path();
+            user.as_deref()
+                .and_then(|user| find_in(user.parent()?))
+                .or_else(|| find_in(Path::new(SYSTEM_CONFIG_DIR)))
+                .or(user)
+        })
+    }
+
+    /// Whether the address and a token or a login are set
+    pub fn has_credentials(&self) -> bool {
+        self.hass_address.is_some()
+            && (self.auth_token.is_some()
+                || (self.refresh_token.is_some() && self.oauth_client_id.is_some()))
+    }
+
+    /// `config.json` in the user config directory (`$XDG_CONFIG_HOME`), where
+    /// `config.toml` and `config.yaml` are looked for too
+    pub fn default_path() -> Option<PathBuf> {
+        directories::ProjectDirs::from("fr", "obito1903", env!("CARGO_PKG_NAME"))
+            .map(|project_dir| project_dir.config_dir().join("config.json"))
+    }
+
+    /// Path of the config file, `default_path` when it wasn't loaded from one
+    fn file_path(&self) -> Option<PathBuf> {
+        self.config_file
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(Config::default_path)
+    }
+
+    /// Key of the config file, see [`path_key`]
+    pub fn file_key(&self) -> String {
+        path_key(&self.file_path().unwrap_or_default())
+    }
+
+    /// Write the whole config to `path`, in the format of its extension
+    pub fn write_to(&self, path: &Path) -> Result<(), &'static str> {
+        write_atomic(path, &ConfigFormat::from_path(path).serialize(self)?)
+    }
+
+    /// Registrations made before the device id was derived from the machine
+    /// id keep the `<hostname>_<os>` one they were registered with, which
+    /// unregistered configs switch from. The
+    /// `Unknown` manufacturer and model of the first versions are replaced
+    /// by the DMI ones, and their `Linux` OS and kernel version by the
+    /// os-release ones.
+    fn from_json(value: serde_json::Value) -> serde_json::Result<Self> {
+        let has_device_id = value.get("device_id").is_some();
+        let mut config: Config = serde_json::from_value(value)?;
+        if config.webhook_id.is_some() {
+            if !has_device_id {
+                config.device_id = device::legacy_device_id();
+            }
+        } else if config.device_id == device::legacy_device_id() {
+            if let Some(device_id) = device::device_id() {
+                config.device_id = device_id;
             }
         }
+        config.migrate(device::manufacturer(), device::model(), os::os_release());
+        Ok(config)
     }
 
+    /// Parse a config file. TOML and YAML configs are newer than the legacy
+    /// device id, and get their registration from `state`.
+    fn parse(
+        content: &str,
+        format: ConfigFormat,
+        state: Option<State>,
+    ) -> Result<Self, &'static str> {
+        let mut value = format.parse(content)?;
+        if format == ConfigFormat::Json {
+            return Config::from_json(value).map_err(|_| "Invalid config file");
+        }
+        let registered = state.as_ref().and_then(|state| state.registered.clone());
+        if let Some(state) = state {
+            state.apply(&mut value);
+        }
+        let mut config: Config =
+            serde_json::from_value(value).map_err(|_| "Invalid config file")?;
+        config.migrate(device::manufacturer(), device::model(), os::os_release());
+        // The migrated values can't be saved to the file, only to the state
+        if registered == Some(Registered::from_config(&config)) {
+            config.registration_changed = false;
+        }
+        Ok(config)
+    }
+
+    fn migrate(
+        &mut self,
+        manufacturer: Option<String>,
+        model: Option<String>,
+        os_release: Option<os::OsRelease>,
+    ) {
+        let mut replace = |value: &mut String, new: Option<String>| {
+            if let Some(new) = new.filter(|_| value == UNKNOWN) {
+                *value = new;
+                self.registration_changed = true;
+            }
+        };
+        replace(&mut self.manufacturer, manufacturer);
+        replace(&mut self.model, model);
+        if let Some(os_release) = os_release.filter(|_| self.os_name == LEGACY_OS_NAME) {
+            // Only the version can be updated, the name is sent again with
+            // the next registration
+            self.os_name = os_release.name;
+            if let Some(version) = os_release.version {
+                self.os_version = version;
+            }
+            self.registration_changed = true;
+        }
+    }
+
+    /// Load a JSON, TOML or YAML config, with the runtime state of the last
+    /// two from the state file
     pub fn load_from_file(path: String) -> Result<Self, &'static str> {
         if Path::new(&path).exists() {
-            let file = File::open(&path).unwrap();
-            let reader = BufReader::new(file);
-            let mut config: Config = serde_json::from_reader(reader).unwrap();
+            let content =
+                fs::read_to_string(&path).map_err(|_| "Unable to read the config file")?;
+            let format = ConfigFormat::from_path(Path::new(&path));
+            let state = match format {
+                ConfigFormat::Json => None,
+                _ => State::load(Path::new(&path)),
+            };
+            let mut config = Config::parse(&content, format, state)?;
             config.config_file = Some(path);
             Ok(config)
         } else {
@@ -96,24 +414,21 @@ impl Config {
         }
     }
 
+    /// Save the config back to the JSON file it was loaded from, or only
+    /// its runtime state for TOML and YAML files, which keeps their comments
     pub fn save_to_file(&self) -> Result<(), &'static str> {
-        let config_path = directories::ProjectDirs::from("fr", "obito1903", env!("CARGO_PKG_NAME"))
-            .unwrap()
-            .config_dir()
-            .join("config.json");
-        if config_path.parent().unwrap().exists().not() {
-            fs::create_dir_all(&config_path.parent().unwrap()).unwrap();
+        let path = self.file_path().ok_or("No config directory")?;
+        match ConfigFormat::from_path(&path) {
+            ConfigFormat::Json => self.write_to(&path),
+            _ => State::from_config(self).save(&path),
         }
-        let mut file = File::create(&config_path).unwrap();
-        file.write_all(serde_json::to_string_pretty(&self).unwrap().as_bytes())
-            .unwrap();
-        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Config;
+    use super::{device, os, Config, ConfigFormat, State};
+    use std::os::unix::fs::PermissionsExt;
     #[test]
     fn serialize_test() {
         let config = Config::new();
@@ -128,10 +443,201 @@ mod tests {
         println!("{:?}", config);
     }
     #[test]
+    fn legacy_device_id_test() {
+        let registered =
+            Config::from_json(serde_json::json!({"webhook_id": "abcd", "device_name": "laptop"}))
+                .unwrap();
+        assert_eq!(registered.device_id, device::legacy_device_id());
+        let kept =
+            Config::from_json(serde_json::json!({"webhook_id": "abcd", "device_id": "mine"}))
+                .unwrap();
+        assert_eq!(kept.device_id, "mine");
+    }
+
+    #[test]
+    fn baseline_config_test() {
+        // config.json as written by the first versions
+        let baseline = |webhook_id: Option<&str>| {
+            serde_json::json!({
+                "auth_token": "secret",
+                "webhook_id": webhook_id,
+                "hass_address": "http://hass.local:8123",
+                "device_id": device::legacy_device_id(),
+                "app_id": "rusty-hass-laptop",
+                "app_name": "Rusty Hass Laptop",
+                "app_version": "0.1.0",
+                "device_name": "laptop",
+                "manufacturer": "Unknown",
+                "model": "Unknown",
+                "os_name": "Linux",
+                "os_version": "6.8.9-300.fc40.x86_64",
+                "support_encryption": false
+            })
+        };
+        let registered = Config::from_json(baseline(Some("abcd"))).unwrap();
+        assert_eq!(registered.device_id, device::legacy_device_id());
+        assert_eq!(registered.auth_token.as_deref(), Some("secret"));
+        let unregistered = Config::from_json(baseline(None)).unwrap();
+        assert_eq!(
+            unregistered.device_id,
+            device::device_id().unwrap_or_else(device::legacy_device_id)
+        );
+    }
+    #[test]
     fn save_test() {
         let config = Config::new();
         config.save_to_file().unwrap();
     }
+    #[test]
+    fn write_to_test() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("write.json");
+        let config = Config {
+            hass_address: Some(String::from("http://hass.local:8123")),
+            ..Default::default()
+        };
+        config.write_to(&path).unwrap();
+        let read = Config::load_from_file(path.to_str().unwrap().to_string()).unwrap();
+        assert_eq!(read.hass_address, config.hass_address);
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        // Unchanged, so not written again
+        let modified = metadata.modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        config.write_to(&path).unwrap();
+        assert_eq!(
+            std::fs::metadata(&path).unwrap().modified().unwrap(),
+            modified
+        );
+        // A file readable by others is tightened even when unchanged
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        config.write_to(&path).unwrap();
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o600);
+        assert_eq!(metadata.modified().unwrap(), modified);
+    }
+    #[test]
+    fn save_back_test() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("save.json");
+        Config::default().write_to(&path).unwrap();
+        let mut config = Config::load(Some(path.clone())).unwrap();
+        config.webhook_id = Some(String::from("abcd"));
+        config.save_to_file().unwrap();
+        let read = Config::load(Some(path.clone())).unwrap();
+        assert_eq!(read.webhook_id.as_deref(), Some("abcd"));
+        std::fs::remove_file(&path).unwrap();
+        assert!(Config::load(Some(path)).is_err());
+    }
+    #[test]
+    fn migrate_test() {
+        let mut config = Config {
+            manufacturer: String::from("Unknown"),
+            model: String::from("XPS 13 9310"),
+            ..Default::default()
+        };
+        config.migrate(None, Some(String::from("XPS 13 9315")), None);
+        assert_eq!(config.manufacturer, "Unknown");
+        assert_eq!(config.model, "XPS 13 9310");
+        assert!(!config.registration_changed);
+
+        config.migrate(Some(String::from("Dell Inc.")), None, None);
+        assert_eq!(config.manufacturer, "Dell Inc.");
+        assert!(config.registration_changed);
+    }
+
+    #[test]
+    fn migrate_os_test() {
+        let fedora = || {
+            Some(os::OsRelease {
+                name: String::from("Fedora Linux"),
+                version: Some(String::from("40")),
+            })
+        };
+        let mut config = Config {
+            os_name: String::from("Linux"),
+            os_version: String::from("6.8.9-300.fc40.x86_64"),
+            ..Default::default()
+        };
+        config.migrate(None, None, fedora());
+        assert_eq!(config.os_name, "Fedora Linux");
+        assert_eq!(config.os_version, "40");
+        assert!(config.registration_changed);
+
+        let mut config = Config {
+            os_name: String::from("Arch Linux"),
+            os_version: String::from("rolling"),
+            ..Default::default()
+        };
+        config.migrate(None, None, fedora());
+        assert_eq!(config.os_name, "Arch Linux");
+        assert!(!config.registration_changed);
+    }
+
+    #[test]
+    fn file_key_test() {
+        let config = |path: &str| Config {
+            config_file: Some(String::from(path)),
+            ..Default::default()
+        };
+        let key = config("/etc/rusty-hass-laptop/config.toml").file_key();
+        assert_eq!(key.len(), 16);
+        assert_eq!(key, config("/etc/rusty-hass-laptop/config.toml").file_key());
+        assert_ne!(key, config("/etc/rusty-hass-laptop/config.yaml").file_key());
+    }
+
+    #[test]
+    fn toml_state_test() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("state.json");
+        State {
+            webhook_id: Some(String::from("abcd")),
+            ..Default::default()
+        }
+        .save_to(&path)
+        .unwrap();
+        let state = State::read(&path);
+        let content = "hass_address = \"http://hass.local:8123\"\nauth_token = \"secret\"\n";
+        let config = Config::parse(content, ConfigFormat::Toml, state).unwrap();
+        assert_eq!(config.webhook_id.as_deref(), Some("abcd"));
+        // Registered from a TOML config, so never with the legacy device id
+        assert_eq!(config.device_id, Config::default().device_id);
+    }
+
+    #[test]
+    fn registered_state_test() {
+        let content = "manufacturer = \"Unknown\"\nos_name = \"Linux\"\n";
+        let first = Config::parse(content, ConfigFormat::Toml, None).unwrap();
+        // Pushed once, then known from the state
+        let state = State::from_config(&first);
+        let config = Config::parse(content, ConfigFormat::Toml, Some(state)).unwrap();
+        assert!(!config.registration_changed);
+    }
+
+    #[test]
+    fn state_test() {
+        let state = State {
+            webhook_id: Some(String::from("abcd")),
+            webhook_secret: Some(String::from("secret")),
+            ..Default::default()
+        };
+        let mut value = serde_json::json!({"hass_address": "http://hass.local:8123"});
+        state.clone().apply(&mut value);
+        let config = Config::from_json(value).unwrap();
+        assert_eq!(State::from_config(&config).webhook_id, state.webhook_id);
+        assert_eq!(
+            State::from_config(&config).webhook_secret,
+            state.webhook_secret
+        );
+
+        // A registration written in the file is kept
+        let mut value = serde_json::json!({"webhook_id": "from-config"});
+        state.apply(&mut value);
+        let config = Config::from_json(value).unwrap();
+        assert_eq!(config.webhook_id.as_deref(), Some("from-config"));
+        assert_eq!(config.webhook_secret, None);
+    }
+
     #[test]
     fn read_test() {
         let config = Config::new();
@@ -0,0 +1,417 @@
+//! Device tracker, sending the laptop position with the `update_location`
+//! webhook. The position comes from GeoClue2 on the system D-Bus, or from a
+//! fixed location set in the config. Alternatively, the Wi-Fi network the
+//! laptop is connected to can be mapped to a Home Assistant zone.
+
+use crate::{
+    hass::{Client, LocationData, Zone},
+    wifi::{self, AccessPoint},
+};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+use zbus::{proxy, zvariant::OwnedObjectPath, Connection};
+
+/// Mean earth radius, in meters
+const EARTH_RADIUS: f64 = 6_371_000.0;
+/// GeoClue `GCLUE_ACCURACY_LEVEL_EXACT`
+const ACCURACY_EXACT: u32 = 8;
+/// Interval between two checks of the Wi-Fi network
+const WIFI_INTERVAL: Duration = Duration::from_secs(60);
+/// Interval between two fetches of the Home Assistant zones
+const ZONES_INTERVAL: Duration = Duration::from_secs(3600);
+/// Delay before the first retry after an error, doubled at each new error
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(30);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(1800);
+/// `location_name` of a device outside of every zone
+const NOT_HOME: &str = "not_home";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LocationConfig {
+    /// Send the laptop position to Home Assistant
+    pub enabled: bool,
+    /// Fixed position to send instead of asking GeoClue
+    pub fixed: Option<Location>,
+    /// Minimum distance in meters between two updates
+    pub min_distance: f64,
+    /// Zone (entity id or name) by Wi-Fi SSID or BSSID, replacing GeoClue
+    pub wifi_zones: BTreeMap<String, String>,
+}
+
+impl Default for LocationConfig {
+    fn default() -> Self {
+        LocationConfig {
+            enabled: false,
+            fixed: None,
+            min_distance: 100.0,
+            wifi_zones: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    /// Accuracy radius in meters
+    pub accuracy: f64,
+    pub altitude: Option<f64>,
+}
+
+impl Location {
+    /// Great-circle distance in meters (haversine formula)
+    pub fn distance(&self, other: &Location) -> f64 {
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.longitude - self.longitude).to_radians();
+        let a = (dlat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().asin()
+    }
+
+    pub fn to_update(&self) -> LocationData {
+        LocationData {
+            gps: Some([self.latitude, self.longitude]),
+            gps_accuracy: Some(self.accuracy.round() as u32),
+            altitude: self.altitude,
+            location_name: None,
+        }
+    }
+}
+
+/// Only let through the locations far enough from the last sent one
+pub struct Throttle {
+    min_distance: f64,
+    last: Option<Location>,
+}
+
+impl Throttle {
+    pub fn new(min_distance: f64) -> Self {
+        Throttle {
+            min_distance,
+            last: None,
+        }
+    }
+
+    pub fn should_send(&mut self, location: &Location) -> bool {
+        let moved = self
+            .last
+            .as_ref()
+            .is_none_or(|last| last.distance(location) >= self.min_distance);
+        if moved {
+            self.last = Some(location.clone());
+        }
+        moved
+    }
+}
+
+#[proxy(
+    interface = "org.freedesktop.GeoClue2.Manager",
+    default_service = "org.freedesktop.GeoClue2",
+    default_path = "/org/freedesktop/GeoClue2/Manager"
+)]
+trait GeoClueManager {
+    fn get_client(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.GeoClue2.Client",
+    default_service = "org.freedesktop.GeoClue2"
+)]
+trait GeoClueClient {
+    fn start(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn set_desktop_id(&self, desktop_id: &str) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn set_requested_accuracy_level(&self, level: u32) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn set_distance_threshold(&self, meters: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn location_updated(&self, old: OwnedObjectPath, new: OwnedObjectPath) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.GeoClue2.Location",
+    default_service = "org.freedesktop.GeoClue2"
+)]
+trait GeoClueLocation {
+    #[zbus(property)]
+    fn latitude(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn longitude(&self) -> zbus::Result<f64>;
+
+    #[zbus(property)]
+    fn accuracy(&self) -> zbus::Result<f64>;
+
+    /// `-f64::MAX` when unknown
+    #[zbus(property)]
+    fn altitude(&self) -> zbus::Result<f64>;
+}
+
+async fn read_location(connection: &Connection, path: OwnedObjectPath) -> zbus::Result<Location> {
+    let location = GeoClueLocationProxy::builder(connection)
+        .path(path)?
+        .build()
+        .await?;
+    let altitude = location.altitude().await?;
+    Ok(Location {
+        latitude: location.latitude().await?,
+        longitude: location.longitude().await?,
+        accuracy: location.accuracy().await?,
+        altitude: (altitude > -f64::MAX).then_some(altitude),
+    })
+}
+
+async fn send(client: &Client, throttle: &mut Throttle, location: &Location) {
+    if throttle.should_send(location) {
+        if let Err(err) = client.update_location(&location.to_update()).await {
+            println!("Unable to update location: {}", err);
+        }
+    }
+}
+
+async fn follow_geoclue(
+    client: &Client,
+    config: &LocationConfig,
+    throttle: &mut Throttle,
+) -> zbus::Result<()> {
+    let connection = Connection::system().await?;
+    let path = GeoClueManagerProxy::new(&connection)
+        .await?
+        .get_client()
+        .await?;
+    let geoclue = GeoClueClientProxy::builder(&connection)
+        .path(path)?
+        .build()
+        .await?;
+    geoclue.set_desktop_id(env!("CARGO_PKG_NAME")).await?;
+    geoclue.set_requested_accuracy_level(ACCURACY_EXACT).await?;
+    geoclue
+        .set_distance_threshold(config.min_distance as u32)
+        .await?;
+    let mut updates = geoclue.receive_location_updated().await?;
+    geoclue.start().await?;
+    while let Some(update) = updates.next().await {
+        let location = read_location(&connection, update.args()?.new).await?;
+        send(client, throttle, &location).await;
+    }
+    Ok(())
+}
+
+/// `location_name` for the access point, the BSSID taking precedence over
+/// the SSID. Zones unknown to Home Assistant are ignored.
+pub fn zone_for(
+    wifi_zones: &BTreeMap<String, String>,
+    zones: &[Zone],
+    access_point: Option<&AccessPoint>,
+) -> String {
+    access_point
+        .and_then(|ap| {
+            wifi_zones
+                .iter()
+                .find(|(network, _)| network.eq_ignore_ascii_case(&ap.bssid))
+                .or_else(|| wifi_zones.iter().find(|(network, _)| **network == ap.ssid))
+        })
+        .and_then(|(_, name)| zones.iter().find(|zone| zone.is_named(name)))
+        .map_or_else(|| String::from(NOT_HOME), Zone::location_name)
+}
+
+/// Follow the Wi-Fi network, the zones being fetched again every
+/// `ZONES_INTERVAL` and on each new call
+async fn follow_wifi(
+    client: &Client,
+    config: &LocationConfig,
+    last: &mut Option<String>,
+) -> zbus::Result<()> {
+    let connection = zbus::Connection::system().await?;
+    let mut zones = None;
+    let mut zones_fetched = Instant::now();
+    loop {
+        if zones.is_some() && zones_fetched.elapsed() >= ZONES_INTERVAL {
+            zones = None;
+        }
+        if zones.is_none() {
+            match client.get_zones().await {
+                Ok(fetched) => {
+                    zones = Some(fetched);
+                    zones_fetched = Instant::now();
+                }
+                Err(err) => println!("Unable to get the zones: {}", err),
+            }
+        }
+        if let Some(zones) = &zones {
+            let access_point = wifi::active_access_point(&connection).await?;
+            let name = zone_for(&config.wifi_zones, zones, access_point.as_ref());
+            if last.as_ref() != Some(&name) {
+                let update = LocationData {
+                    gps: None,
+                    gps_accuracy: None,
+                    altitude: None,
+                    location_name: Some(name.clone()),
+                };
+                match client.update_location(&update).await {
+                    Ok(()) => *last = Some(name),
+                    Err(err) => println!("Unable to update location: {}", err),
+                }
+            }
+        }
+        time::sleep(WIFI_INTERVAL).await;
+    }
+}
+
+/// Send the laptop position until shutdown
+pub async fn track(client: Client, config: LocationConfig, shutdown: CancellationToken) {
+    let mut throttle = Throttle::new(config.min_distance);
+    if let Some(fixed) = &config.fixed {
+        send(&client, &mut throttle, fixed).await;
+        return;
+    }
+    let mut retry_delay = MIN_RETRY_DELAY;
+    if !config.wifi_zones.is_empty() {
+        println!("Reporting the zone of the Wi-Fi network, GeoClue is not used");
+        let mut last = None;
+        loop {
+            let started = Instant::now();
+            tokio::select! {
+                _ = shutdown.cancelled() => return,
+                Err(err) = follow_wifi(&client, &config, &mut last) => {
+                    println!("Unable to get the Wi-Fi network from NetworkManager: {}", err);
+                }
+            }
+            if !backoff(&mut retry_delay, started, &shutdown).await {
+                return;
+            }
+        }
+    }
+    loop {
+        let started = Instant::now();
+        tokio::select! {
+            _ = shutdown.cancelled() => return,
+            res = follow_geoclue(&client, &config, &mut throttle) => {
+                if let Err(err) = res {
+                    println!("Unable to get the location from GeoClue: {}", err);
+                }
+            }
+        }
+        if !backoff(&mut retry_delay, started, &shutdown).await {
+            return;
+        }
+    }
+}
+
+/// Wait before following the position again, `false` on shutdown. The
+/// delay is reset when the last attempt lasted longer than the longest one.
+async fn backoff(
+    retry_delay: &mut Duration,
+    started: Instant,
+    shutdown: &CancellationToken,
+) -> bool {
+    if started.elapsed() > MAX_RETRY_DELAY {
+        *retry_delay = MIN_RETRY_DELAY;
+    }
+    tokio::select! {
+        _ = shutdown.cancelled() => return false,
+        _ = time::sleep(*retry_delay) => (),
+    }
+    *retry_delay = (*retry_delay * 2).min(MAX_RETRY_DELAY);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{zone_for, Location, Throttle};
+    use crate::{hass::Zone, wifi::AccessPoint};
+    use serde_json::json;
+    use std::collections::BTreeMap;
+
+    fn at(latitude: f64, longitude: f64) -> Location {
+        Location {
+            latitude,
+            longitude,
+            accuracy: 20.0,
+            altitude: None,
+        }
+    }
+
+    #[test]
+    fn distance_test() {
+        let paris = at(48.8566, 2.3522);
+        let london = at(51.5074, -0.1278);
+        let distance = paris.distance(&london);
+        assert!((distance - 343_500.0).abs() < 1_000.0, "{}", distance);
+        assert_eq!(paris.distance(&paris), 0.0);
+    }
+
+    #[test]
+    fn throttle_test() {
+        let mut throttle = Throttle::new(100.0);
+        assert!(throttle.should_send(&at(48.8566, 2.3522)));
+        // ~55 m north
+        assert!(!throttle.should_send(&at(48.8571, 2.3522)));
+        // ~111 m north of the first one
+        assert!(throttle.should_send(&at(48.8576, 2.3522)));
+    }
+
+    #[test]
+    fn wifi_zone_test() {
+        let zones: Vec<Zone> = serde_json::from_value(json!([
+            {
+                "entity_id": "zone.home",
+                "state": "zoning",
+                "attributes": {"friendly_name": "Home", "latitude": 48.85, "longitude": 2.35, "radius": 100}
+            },
+            {
+                "entity_id": "zone.work",
+                "state": "zoning",
+                "attributes": {"friendly_name": "Office", "latitude": 48.87, "longitude": 2.33, "radius": 50}
+            }
+        ]))
+        .unwrap();
+        let mut wifi_zones = BTreeMap::new();
+        wifi_zones.insert(String::from("HomeNet"), String::from("zone.home"));
+        wifi_zones.insert(String::from("Corp"), String::from("office"));
+        wifi_zones.insert(String::from("AA:BB:CC:DD:EE:FF"), String::from("Office"));
+        wifi_zones.insert(String::from("Cafe"), String::from("zone.cafe"));
+        let ap = |ssid: &str, bssid: &str| AccessPoint {
+            ssid: ssid.to_string(),
+            bssid: bssid.to_string(),
+        };
+
+        assert_eq!(
+            zone_for(
+                &wifi_zones,
+                &zones,
+                Some(&ap("HomeNet", "00:11:22:33:44:55"))
+            ),
+            "home"
+        );
+        assert_eq!(
+            zone_for(&wifi_zones, &zones, Some(&ap("Corp", "00:11:22:33:44:55"))),
+            "Office"
+        );
+        // The BSSID wins over the SSID
+        assert_eq!(
+            zone_for(
+                &wifi_zones,
+                &zones,
+                Some(&ap("HomeNet", "aa:bb:cc:dd:ee:ff"))
+            ),
+            "Office"
+        );
+        assert_eq!(
+            zone_for(&wifi_zones, &zones, Some(&ap("Cafe", "00:11:22:33:44:55"))),
+            "not_home"
+        );
+        assert_eq!(zone_for(&wifi_zones, &zones, None), "not_home");
+    }
+}
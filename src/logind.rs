@@ -0,0 +1,123 @@
+//! systemd-logind over the system D-Bus
+
+use futures::StreamExt;
+use zbus::{
+    proxy,
+    zvariant::{OwnedFd, OwnedObjectPath},
+    Connection,
+};
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+pub trait Manager {
+    fn inhibit(&self, what: &str, who: &str, why: &str, mode: &str) -> zbus::Result<OwnedFd>;
+
+    fn suspend(&self, interactive: bool) -> zbus::Result<()>;
+
+    fn power_off(&self, interactive: bool) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.User",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1/user/self"
+)]
+pub trait User {
+    /// Primary (graphical) session of the user
+    #[zbus(property)]
+    fn display(&self) -> zbus::Result<(String, OwnedObjectPath)>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+pub trait Session {
+    fn lock(&self) -> zbus::Result<()>;
+
+    #[zbus(property)]
+    fn locked_hint(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn idle_hint(&self) -> zbus::Result<bool>;
+
+    #[zbus(property)]
+    fn active(&self) -> zbus::Result<bool>;
+
+    /// `CLOCK_REALTIME` timestamp in µs of the last idle hint change
+    #[zbus(property)]
+    fn idle_since_hint(&self) -> zbus::Result<u64>;
+}
+
+/// Graphical session of the user running the app
+pub async fn display_session(connection: &Connection) -> zbus::Result<SessionProxy<'static>> {
+    let (_, path) = UserProxy::new(connection).await?.display().await?;
+    SessionProxy::builder(connection).path(path)?.build().await
+}
+
+/// Blocking version of [`display_session`], for the providers
+pub fn display_session_blocking() -> zbus::Result<SessionProxyBlocking<'static>> {
+    let connection = zbus::blocking::Connection::system()?;
+    let (_, path) = UserProxyBlocking::new(&connection)?.display()?;
+    SessionProxyBlocking::builder(&connection)
+        .path(path)?
+        .build()
+}
+
+/// Follow `PrepareForSleep`, holding a delay inhibitor lock so a last update
+/// can be sent before the system actually goes to sleep.
+pub struct SleepWatcher {
+    manager: ManagerProxy<'static>,
+    signals: PrepareForSleepStream,
+    inhibitor: Option<OwnedFd>,
+}
+
+impl SleepWatcher {
+    pub async fn new() -> zbus::Result<Self> {
+        let connection = Connection::system().await?;
+        let manager = ManagerProxy::new(&connection).await?;
+        let signals = manager.receive_prepare_for_sleep().await?;
+        let mut watcher = SleepWatcher {
+            manager,
+            signals,
+            inhibitor: None,
+        };
+        watcher.inhibit().await?;
+        Ok(watcher)
+    }
+
+    /// Wait for the next `PrepareForSleep`, `true` before suspend and `false`
+    /// after resume
+    pub async fn next(&mut self) -> Option<bool> {
+        let signal = self.signals.next().await?;
+        signal.args().ok().map(|args| args.start)
+    }
+
+    /// Take the delay lock, to be done again after each resume
+    pub async fn inhibit(&mut self) -> zbus::Result<()> {
+        if self.inhibitor.is_none() {
+            self.inhibitor = Some(
+                self.manager
+                    .inhibit(
+                        "sleep",
+                        env!("CARGO_PKG_NAME"),
+                        "Report the laptop state before sleeping",
+                        "delay",
+                    )
+                    .await?,
+            );
+        }
+        Ok(())
+    }
+
+    /// Drop the delay lock, letting the system go to sleep
+    pub fn release(&mut self) {
+        self.inhibitor = None;
+    }
+}
@@ -1,31 +1,485 @@
+use clap::{Parser, Subcommand};
+use futures::future;
 use rusty_hass_laptop::{
-    hass,
-    sensors::Provider,
-    sensors::{battery::BatteryProvider, cpu::CpuProvider},
+    commands::{self, actions::ActionHandler, Dispatcher},
+    hass, location,
+    logind::SleepWatcher,
+    notify::DesktopNotifier,
+    scheduler::Scheduler,
+    sensors::{
+        battery::{has_battery, BatteryProvider},
+        cpu::CpuProvider,
+        idle::IdleProvider,
+        network::NetworkProvider,
+        session::SessionProvider,
+        sleep::SleepProvider,
+        storage::StorageProvider,
+        system::SystemProvider,
+    },
+    sensors::{DynSensorData, Provider, SensorData},
+    status::Status,
+    uevent::{self, Uevent},
     Config,
 };
+use serde_json::Value;
+use std::{
+    path::PathBuf,
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+use tokio::{
+    signal::{self, unix},
+    sync::mpsc::UnboundedReceiver,
+    task, time,
+};
+use tokio_util::sync::CancellationToken;
+
+mod init;
+
+/// Delay before registering the sensors again, doubled at each failure
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(10);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(300);
+
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// Config file to use instead of the default one
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create the config interactively and register the app
+    Init,
+    /// Send the sensors to Home Assistant until stopped (the default)
+    Run {
+        /// Send a single round of updates, then exit
+        #[arg(long)]
+        once: bool,
+    },
+    /// Register the app again, replacing the current registration
+    Register,
+    /// Show the config, the registration and the last updates
+    Status,
+    /// Print every sensor with its current value
+    ListSensors {
+        /// Print the sensors as JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check the address and token against Home Assistant
+    TestConnection,
+    /// Remove the app from Home Assistant and forget the registration
+    Unregister,
+    /// Fire an event through the registered device, for scripts
+    FireEvent {
+        event_type: String,
+        /// Event data, as a JSON object
+        data: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<(), battery::Error> {
+    let cli = Cli::parse();
+    let path = cli.config;
+    match cli.command.unwrap_or(Command::Run { once: false }) {
+        // The config file doesn't exist yet
+        Command::Init => match Config::requested_path(path).or_else(Config::default_path) {
+            Some(path) => init::run(&path).await,
+            None => eprintln!("No config directory, use --config"),
+        },
+        Command::Run { once } => return run(load_config(path), once).await,
+        Command::Register => register(load_config(path)).await,
+        Command::Status => status(&load_config(path)),
+        Command::ListSensors { json } => list_sensors(json),
+        Command::TestConnection => test_connection(load_config(path)).await,
+        Command::Unregister => unregister(load_config(path)).await,
+        Command::FireEvent { event_type, data } => {
+            fire_event(load_config(path), &event_type, data).await
+        }
+    }
+    Ok(())
+}
+
+fn load_config(path: Option<PathBuf>) -> Config {
+    Config::load(path.clone()).unwrap_or_else(|err| {
+        let path = Config::resolve_path(path).unwrap_or_default();
+        eprintln!("{}: {}", path.display(), err);
+        process::exit(1);
+    })
+}
+
+fn exit_without_credentials(config: &Config) {
+    if !config.has_credentials() {
+        eprintln!("Home Assistant is not configured yet, run rusty-hass-laptop init first");
+        process::exit(1);
+    }
+}
+
+fn exit_unregistered(config: &Config) {
+    if config.webhook_id.is_none() {
+        eprintln!("The app is not registered yet, run rusty-hass-laptop register first");
+        process::exit(1);
+    }
+}
+
+/// Save the registration, which would be lost otherwise, e.g. with a config
+/// in /etc not writable by the user
+fn save_config(config: &Config) {
+    if let Err(err) = config.save_to_file() {
+        eprintln!(
+            "Unable to save the registration to {}: {}",
+            config.config_file.as_deref().unwrap_or("the config file"),
+            err
+        );
+        eprintln!("Run as a user who can write it, or use another config with --config");
+        process::exit(1);
+    }
+}
+
+/// The providers of every sensor, with the handle of the sleep sensor
+fn providers() -> (Vec<Box<dyn Provider>>, Arc<AtomicBool>) {
+    let sleep = SleepProvider::new();
+    let sleeping = sleep.handle();
+    let mut providers: Vec<Box<dyn Provider>> = vec![
+        Box::new(CpuProvider::new()),
+        Box::new(sleep),
+        Box::new(SessionProvider::new()),
+        Box::new(IdleProvider::new()),
+        Box::new(SystemProvider::new()),
+        Box::new(NetworkProvider::new()),
+        Box::new(StorageProvider::new()),
+    ];
+    if has_battery() {
+        providers.insert(0, Box::new(BatteryProvider::new()));
+    }
+    task::block_in_place(|| {
+        for provider in providers.iter_mut() {
+            if let Err(err) = provider.update_all() {
+                println!("Unable to update provider: {}", err);
+            }
+        }
+    });
+    (providers, sleeping)
+}
+
+/// Register the app and save its webhook, exiting on failure
+async fn register_app(client: &mut hass::Client) {
+    if let Err(err) = client.register_app().await {
+        eprintln!("Unable to register the app: {}", err);
+        process::exit(1);
+    }
+    exit_unregistered(&client.config);
+    save_config(&client.config);
+}
+
+/// Register the sensors, retrying while Home Assistant is unreachable (e.g.
+/// at boot)
+async fn register_providers(client: &hass::Client, providers: &[Box<dyn Provider>]) {
+    let mut retry_delay = MIN_RETRY_DELAY;
+    while let Err(err) = client.register_providers(providers).await {
+        println!(
+            "Unable to register the sensors, retrying in {}s: {}",
+            retry_delay.as_secs(),
+            err
+        );
+        time::sleep(retry_delay).await;
+        retry_delay = (retry_delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+async fn register(config: Config) {
+    exit_without_credentials(&config);
+    let mut client = hass::Client::new(config).unwrap();
+    register_app(&mut client).await;
+    let (providers, _) = providers();
+    if let Err(err) = client.register_providers(&providers).await {
+        eprintln!("Unable to register the sensors: {}", err);
+        process::exit(1);
+    }
+    println!("Registered as {}", client.config.device_name);
+}
+
+fn status(config: &Config) {
+    println!(
+        "Config file:  {}",
+        config.config_file.as_deref().unwrap_or("-")
+    );
+    println!(
+        "Home Assistant: {}",
+        config.hass_address.as_deref().unwrap_or("not set")
+    );
+    println!(
+        "Device:       {} ({})",
+        config.device_name, config.device_id
+    );
+    match &config.webhook_id {
+        Some(webhook_id) => println!("Registered:   yes, webhook {}", webhook_id),
+        None => println!("Registered:   no"),
+    }
+    match Status::load(config) {
+        Some(Status {
+            last_update: Some(last_update),
+            updates,
+            error,
+        }) => println!(
+            "Last update:  {} updates at {} (unix time), {}",
+            updates,
+            last_update,
+            error.as_deref().unwrap_or("ok")
+        ),
+        _ => println!("Last update:  never"),
+    }
+}
+
+fn list_sensors(json: bool) {
+    let (providers, _) = providers();
+    let sensors: Vec<DynSensorData> = providers
+        .iter()
+        .flat_map(|provider| provider.sensors())
+        .map(|sensor| sensor.register_info())
+        .collect();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&sensors).unwrap());
+        return;
+    }
+    println!("{:<28} {:<24} {:>10} UNIT", "ID", "NAME", "STATE");
+    for sensor in &sensors {
+        if let SensorData::RegisterSensor { data } = sensor {
+            let state = match &data.state {
+                Value::String(state) => state.clone(),
+                state => state.to_string(),
+            };
+            println!(
+                "{:<28} {:<24} {:>10} {}",
+                data.unique_id,
+                data.name,
+                state,
+                data.unit_of_measurement.as_deref().unwrap_or("")
+            );
+        }
+    }
+}
+
+async fn test_connection(config: Config) {
+    if !config.has_credentials() {
+        eprintln!("hass_address and auth_token need to be set in the config, or run init");
+        process::exit(1);
+    }
+    let client = hass::Client::new(config).unwrap();
+    match client.check_api().await {
+        Ok(message) => println!("REST API: {}", message),
+        Err(err) => {
+            eprintln!("REST API: {}", err);
+            process::exit(1);
+        }
+    }
+    if client.config.webhook_id.is_none() {
+        println!("Webhook: not registered");
+        return;
+    }
+    match client.get_config().await {
+        Ok(hass_config) => println!(
+            "Webhook: Home Assistant {} ({})",
+            hass_config.version, hass_config.location_name
+        ),
+        Err(err) => {
+            eprintln!("Webhook: {}", err);
+            process::exit(1);
+        }
+    }
+}
+
+async fn unregister(config: Config) {
+    exit_without_credentials(&config);
+    exit_unregistered(&config);
+    let mut client = hass::Client::new(config).unwrap();
+    if let Err(err) = client.unregister_app().await {
+        eprintln!("Unable to unregister the app: {}", err);
+        process::exit(1);
+    }
+    save_config(&client.config);
+    println!("Unregistered {}", client.config.device_name);
+}
+
+async fn fire_event(config: Config, event_type: &str, data: Option<String>) {
+    let event_data = match data {
+        None => Value::Object(Default::default()),
+        Some(data) => serde_json::from_str(&data).unwrap_or_else(|err| {
+            eprintln!("Invalid event data: {}", err);
+            process::exit(2);
+        }),
+    };
+    exit_unregistered(&config);
+    let client = hass::Client::new(config).unwrap();
+    if let Err(err) = client.fire_event(event_type, event_data).await {
+        eprintln!("Unable to fire {}: {}", event_type, err);
+        process::exit(1);
+    }
+}
+
+async fn run(config: Config, once: bool) -> Result<(), battery::Error> {
+    exit_without_credentials(&config);
+    let mut client = hass::Client::new(config).unwrap();
+    if client.config.webhook_id.is_none() {
+        register_app(&mut client).await;
+    } else if client.config.registration_changed {
+        match client.update_registration().await {
+            Ok(()) => {
+                if let Err(err) = client.config.save_to_file() {
+                    println!("Unable to save the config: {}", err);
+                }
+            }
+            Err(err) => println!("Unable to update the registration: {}", err),
+        }
+    }
+    match client.get_config().await {
+        Ok(hass_config) if !hass_config.has_component("mobile_app") => {
+            println!("The mobile_app integration is not loaded in Home Assistant")
+        }
+        Ok(hass_config) => println!(
+            "Connected to Home Assistant {} ({})",
+            hass_config.version, hass_config.location_name
+        ),
+        Err(err) => println!("Unable to get the Home Assistant config: {}", err),
+    }
+
+    let (providers, sleeping) = providers();
+    register_providers(&client, &providers).await;
+    let mut scheduler = Scheduler::new(providers);
+    if once {
+        let updates = task::block_in_place(|| scheduler.poll_due(Instant::now()));
+        send_updates(&client, &updates).await;
+        return Ok(());
+    }
+    // Polling alone is used as a fallback when the netlink socket is unavailable
+    let mut uevents = match uevent::spawn_listener(scheduler.subsystems()) {
+        Ok(uevents) => Some(uevents),
+        Err(err) => {
+            println!("Unable to listen for uevents, polling only: {}", err);
+            None
+        }
+    };
+    let mut sleep_watcher = match SleepWatcher::new().await {
+        Ok(watcher) => Some(watcher),
+        Err(err) => {
+            println!("Unable to watch logind sleep signals: {}", err);
+            None
+        }
+    };
+
+    let shutdown = CancellationToken::new();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            wait_for_signal().await;
+            shutdown.cancel();
+        }
+    });
+    let mut dispatcher = Dispatcher::new();
+    match DesktopNotifier::new(client.clone()).await {
+        Ok(notifier) => dispatcher.add(Box::new(notifier)),
+        Err(err) => println!("Unable to show desktop notifications: {}", err),
+    }
+    dispatcher.add(Box::new(ActionHandler::new(
+        client.clone(),
+        client.config.actions.clone(),
+    )));
+    if client.config.location.enabled {
+        tokio::spawn(location::track(
+            client.clone(),
+            client.config.location.clone(),
+            shutdown.clone(),
+        ));
+    }
+    let commands = tokio::spawn(commands::listen(
+        client.clone(),
+        dispatcher,
+        shutdown.clone(),
+    ));
 
-fn main() -> Result<(), battery::Error> {
-    let mut client = hass::Client::new(Config::new()).unwrap();
-    match client.config.webhook_id {
-        None => client.register_app().unwrap(),
-        _ => (),
-    }
-    client.config.save_to_file().unwrap();
-
-    let mut battery = BatteryProvider::new();
-    let mut cpu = CpuProvider::new();
-    battery.update_all().unwrap();
-    cpu.update_all().unwrap();
-    client.register_sensor(&battery.level).unwrap();
-    client.register_sensor(&cpu.cpu_freq).unwrap();
-    client.register_sensor(&cpu.cpu_cores).unwrap();
     loop {
-        battery.update_all().unwrap();
-        println!("{:?}", battery.level.state);
-        client.update_sensor(&battery.level).unwrap();
-        client.update_sensor(&cpu.cpu_freq).unwrap();
-        client.update_sensor(&cpu.cpu_cores).unwrap();
-        std::thread::sleep(std::time::Duration::from_secs(60));
+        let updates = task::block_in_place(|| scheduler.poll_due(Instant::now()));
+        // Not raced against the shutdown, so pending updates are flushed
+        // before exiting (each request is bounded by `request_timeout`)
+        send_updates(&client, &updates).await;
+        let deadline = time::Instant::from_std(scheduler.next_deadline());
+        tokio::select! {
+            _ = shutdown.cancelled() => break,
+            _ = time::sleep_until(deadline) => (),
+            event = next_uevent(&mut uevents) => match event {
+                Some(event) => scheduler.trigger(&event.subsystem),
+                None => {
+                    println!("Uevent listener stopped, polling only");
+                    uevents = None;
+                }
+            },
+            Some(start) = next_sleep(&mut sleep_watcher) => {
+                sleeping.store(start, Ordering::Relaxed);
+                // Everything is polled and reported again, either as a last
+                // update before suspend or to replace stale data after resume
+                scheduler.reset();
+                let watcher = sleep_watcher.as_mut().unwrap();
+                if start {
+                    let updates = task::block_in_place(|| scheduler.poll_due(Instant::now()));
+                    send_updates(&client, &updates).await;
+                    watcher.release();
+                } else if let Err(err) = watcher.inhibit().await {
+                    println!("Unable to take the logind sleep lock: {}", err);
+                }
+            }
+        }
+    }
+
+    commands.await.unwrap();
+    println!("Shutting down, marking sensors as unavailable");
+    if let Err(err) = client.mark_unavailable(scheduler.providers()).await {
+        println!("Unable to mark sensors as unavailable: {}", err);
+    }
+    Ok(())
+}
+
+/// Send the updates, keeping the result for the `status` command
+async fn send_updates(client: &hass::Client, updates: &[DynSensorData]) {
+    if updates.is_empty() {
+        return;
+    }
+    let res = client.send_updates(updates).await;
+    if let Err(err) = &res {
+        println!("Unable to send updates: {}", err);
+    }
+    if let Err(err) = Status::record(updates.len(), &res).save(&client.config) {
+        println!("Unable to save the status: {}", err);
+    }
+}
+
+/// Wait for SIGINT or SIGTERM (sent by systemd when stopping the service)
+async fn wait_for_signal() {
+    let mut sigterm = unix::signal(unix::SignalKind::terminate()).unwrap();
+    tokio::select! {
+        _ = signal::ctrl_c() => (),
+        _ = sigterm.recv() => (),
+    }
+}
+
+async fn next_sleep(watcher: &mut Option<SleepWatcher>) -> Option<bool> {
+    match watcher {
+        Some(watcher) => watcher.next().await,
+        None => future::pending().await,
+    }
+}
+
+async fn next_uevent(uevents: &mut Option<UnboundedReceiver<Uevent>>) -> Option<Uevent> {
+    match uevents {
+        Some(uevents) => uevents.recv().await,
+        None => future::pending().await,
     }
 }
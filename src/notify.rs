@@ -0,0 +1,241 @@
+//! Show the `notify.mobile_app_*` messages as desktop notifications, through
+//! the freedesktop Notifications interface on the session D-Bus.
+
+use crate::{
+    commands::CommandHandler,
+    hass::{websocket::PushMessage, Client},
+};
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::{json, Value as JsonValue};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+use zbus::{proxy, zvariant::Value, Connection};
+
+/// Event fired on Home Assistant when a notification action is clicked
+const ACTION_EVENT: &str = "mobile_app_notification_action";
+
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+pub trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: HashMap<&str, Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    fn close_notification(&self, id: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: String) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+/// Notifications currently displayed, by notification id
+type Displayed = Arc<Mutex<HashMap<u32, PushMessage>>>;
+
+pub struct DesktopNotifier {
+    notifications: NotificationsProxy<'static>,
+    displayed: Displayed,
+}
+
+impl DesktopNotifier {
+    /// Connect to the session bus, and send the clicked actions back to
+    /// Home Assistant as `mobile_app_notification_action` events
+    pub async fn new(client: Client) -> zbus::Result<Self> {
+        let connection = Connection::session().await?;
+        let notifications = NotificationsProxy::new(&connection).await?;
+        let displayed: Displayed = Default::default();
+
+        let mut actions = notifications.receive_action_invoked().await?;
+        let mut closed = notifications.receive_notification_closed().await?;
+        let forwarded = displayed.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    Some(signal) = actions.next() => {
+                        let Ok(args) = signal.args() else { continue };
+                        let message = forwarded.lock().unwrap().get(&args.id).cloned();
+                        if let Some(message) = message {
+                            let event = action_event(&message, &args.action_key);
+                            if let Err(err) = client.fire_event(ACTION_EVENT, event).await {
+                                println!("Unable to send notification action: {}", err);
+                            }
+                        }
+                    }
+                    Some(signal) = closed.next() => {
+                        if let Ok(args) = signal.args() {
+                            forwarded.lock().unwrap().remove(&args.id);
+                        }
+                    }
+                    else => break,
+                }
+            }
+        });
+
+        Ok(DesktopNotifier {
+            notifications,
+            displayed,
+        })
+    }
+
+    /// Id of the displayed notification with the same `tag`, to replace it
+    fn tagged(&self, tag: Option<&str>) -> Option<u32> {
+        let tag = tag?;
+        self.displayed
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, message)| message.data.get("tag").and_then(|t| t.as_str()) == Some(tag))
+            .map(|(id, _)| *id)
+    }
+
+    async fn show(&self, message: &PushMessage) -> zbus::Result<()> {
+        let tag = message.data.get("tag").and_then(|tag| tag.as_str());
+        let replaces_id = self.tagged(tag);
+        let actions = actions(message);
+        let actions: Vec<&str> = actions.iter().map(String::as_str).collect();
+        let mut hints = HashMap::new();
+        hints.insert("urgency", Value::U8(urgency(message)));
+        let id = self
+            .notifications
+            .notify(
+                "Home Assistant",
+                replaces_id.unwrap_or(0),
+                string_data(message, "icon").unwrap_or(""),
+                message.title.as_deref().unwrap_or("Home Assistant"),
+                &message.message,
+                &actions,
+                hints,
+                expire_timeout(message),
+            )
+            .await?;
+        self.displayed.lock().unwrap().insert(id, message.clone());
+        Ok(())
+    }
+
+    async fn clear(&self, message: &PushMessage) -> zbus::Result<()> {
+        let tag = message.data.get("tag").and_then(|tag| tag.as_str());
+        if let Some(id) = self.tagged(tag) {
+            self.notifications.close_notification(id).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl CommandHandler for DesktopNotifier {
+    async fn handle(&self, message: &PushMessage) -> bool {
+        let res = match message.message.as_str() {
+            "clear_notification" => self.clear(message).await,
+            _ if message.command().is_some() => return false,
+            _ => self.show(message).await,
+        };
+        if let Err(err) = res {
+            println!("Unable to show notification: {}", err);
+        }
+        true
+    }
+}
+
+fn string_data<'a>(message: &'a PushMessage, key: &str) -> Option<&'a str> {
+    message.data.get(key).and_then(|value| value.as_str())
+}
+
+/// freedesktop urgency level: 0 low, 1 normal, 2 critical
+fn urgency(message: &PushMessage) -> u8 {
+    match string_data(message, "urgency").or_else(|| string_data(message, "priority")) {
+        Some("low") | Some("min") => 0,
+        Some("critical") | Some("high") | Some("max") => 2,
+        _ => 1,
+    }
+}
+
+/// `timeout` is given in seconds, -1 lets the notification server decide
+fn expire_timeout(message: &PushMessage) -> i32 {
+    message
+        .data
+        .get("timeout")
+        .and_then(|timeout| timeout.as_f64())
+        .map_or(-1, |timeout| (timeout * 1000.0) as i32)
+}
+
+/// Flattened `[key, label, key, label, ...]` list of the notification actions
+fn actions(message: &PushMessage) -> Vec<String> {
+    message
+        .data
+        .get("actions")
+        .and_then(|actions| actions.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|action| {
+            let key = action["action"].as_str()?;
+            let title = action["title"].as_str().unwrap_or(key);
+            Some([key.to_string(), title.to_string()])
+        })
+        .flatten()
+        .collect()
+}
+
+fn action_event(message: &PushMessage, action: &str) -> JsonValue {
+    let mut event = json!({
+        "action": action,
+        "message": message.message,
+        "title": message.title,
+    });
+    if let Some(tag) = message.data.get("tag") {
+        event["tag"] = tag.clone();
+    }
+    event
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{action_event, actions, expire_timeout, urgency};
+    use crate::hass::websocket::PushMessage;
+    use serde_json::json;
+
+    #[test]
+    fn notification_test() {
+        let message: PushMessage = serde_json::from_value(json!({
+            "message": "Washing machine done",
+            "title": "Laundry",
+            "data": {
+                "tag": "laundry",
+                "priority": "high",
+                "timeout": 30,
+                "actions": [
+                    {"action": "DONE", "title": "Emptied"},
+                    {"action": "LATER"}
+                ]
+            }
+        }))
+        .unwrap();
+        assert_eq!(urgency(&message), 2);
+        assert_eq!(expire_timeout(&message), 30000);
+        assert_eq!(actions(&message), vec!["DONE", "Emptied", "LATER", "LATER"]);
+        assert_eq!(
+            action_event(&message, "DONE"),
+            json!({
+                "action": "DONE",
+                "message": "Washing machine done",
+                "title": "Laundry",
+                "tag": "laundry"
+            })
+        );
+    }
+}
@@ -0,0 +1,79 @@
+//! Distribution name and version, from os-release(5).
+
+use std::{collections::BTreeMap, fs};
+
+const OS_RELEASE_PATHS: [&str; 2] = ["/etc/os-release", "/usr/lib/os-release"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsRelease {
+    /// `NAME`, like "Fedora Linux"
+    pub name: String,
+    /// `VERSION_ID`, or `BUILD_ID` for rolling distributions
+    pub version: Option<String>,
+}
+
+/// `KEY=value` lines, with the shell quoting removed from the values
+fn parse_variables(content: &str) -> BTreeMap<&str, String> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once('='))
+        .map(|(key, value)| {
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .or_else(|| {
+                    value
+                        .strip_prefix('\'')
+                        .and_then(|value| value.strip_suffix('\''))
+                })
+                .unwrap_or(value);
+            (key, value.replace("\\\"", "\"").replace("\\\\", "\\"))
+        })
+        .collect()
+}
+
+pub fn parse(content: &str) -> Option<OsRelease> {
+    let mut variables = parse_variables(content);
+    Some(OsRelease {
+        name: variables.remove("NAME")?,
+        version: variables
+            .remove("VERSION_ID")
+            .or_else(|| variables.remove("BUILD_ID")),
+    })
+}
+
+pub fn os_release() -> Option<OsRelease> {
+    OS_RELEASE_PATHS
+        .iter()
+        .find_map(|path| fs::read_to_string(path).ok())
+        .and_then(|content| parse(&content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, OsRelease};
+
+    #[test]
+    fn parse_test() {
+        let fedora = "NAME=\"Fedora Linux\"\nVERSION=\"40 (Workstation Edition)\"\nID=fedora\nVERSION_ID=40\n";
+        assert_eq!(
+            parse(fedora),
+            Some(OsRelease {
+                name: String::from("Fedora Linux"),
+                version: Some(String::from("40")),
+            })
+        );
+        let arch =
+            "# Arch\nNAME='Arch Linux'\nPRETTY_NAME=\"Arch Linux\"\nID=arch\nBUILD_ID=rolling\n";
+        assert_eq!(
+            parse(arch),
+            Some(OsRelease {
+                name: String::from("Arch Linux"),
+                version: Some(String::from("rolling")),
+            })
+        );
+        assert_eq!(parse("ID=unknown\n"), None);
+    }
+}
@@ -0,0 +1,420 @@
+use crate::sensors::{DynSensor, DynSensorData, Provider, ReportPolicy, SensorData};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    thread,
+    time::{Duration, Instant},
+};
+
+/// Drive the providers according to the polling interval of their sensors,
+/// and filter the updates with each sensor report policy.
+pub struct Scheduler {
+    providers: Vec<Box<dyn Provider>>,
+    next_polls: HashMap<String, Instant>,
+    last_reports: HashMap<String, Report>,
+    /// Delay before retrying the sensors whose provider failed to update
+    retry_delays: HashMap<String, Duration>,
+}
+
+/// First retry delay after a failed update, doubled on each new failure up
+/// to the poll interval of the sensor
+const MIN_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+struct Report {
+    state: Value,
+    at: Instant,
+}
+
+impl Scheduler {
+    pub fn new(providers: Vec<Box<dyn Provider>>) -> Self {
+        Scheduler {
+            providers,
+            next_polls: HashMap::new(),
+            last_reports: HashMap::new(),
+            retry_delays: HashMap::new(),
+        }
+    }
+
+    pub fn providers(&self) -> &[Box<dyn Provider>] {
+        &self.providers
+    }
+
+    /// When the next sensor is due
+    pub fn next_deadline(&self) -> Instant {
+        self.providers
+            .iter()
+            .flat_map(|provider| provider.sensors())
+            .map(|sensor| self.next_poll(sensor))
+            .min()
+            .unwrap_or_else(Instant::now)
+    }
+
+    /// Refresh the providers having at least one sensor due, and return the
+    /// updates that should be sent to Home Assistant.
+    ///
+    /// Due providers are refreshed concurrently, each in its own thread. The
+    /// sensors of a provider that failed are retried with a backoff.
+    pub fn poll_due(&mut self, now: Instant) -> Vec<DynSensorData> {
+        let next_polls = &self.next_polls;
+        let due: Vec<(&mut Box<dyn Provider>, Vec<String>)> = self
+            .providers
+            .iter_mut()
+            .map(|provider| {
+                let due: Vec<String> = provider
+                    .sensors()
+                    .into_iter()
+                    .map(|sensor| sensor.unique_id())
+                    .filter(|unique_id| next_polls.get(unique_id).is_none_or(|next| *next <= now))
+                    .collect();
+                (provider, due)
+            })
+            .filter(|(_, due)| !due.is_empty())
+            .collect();
+        let polled: Vec<(&mut Box<dyn Provider>, Vec<String>, bool)> = thread::scope(|scope| {
+            let handles: Vec<_> = due
+                .into_iter()
+                .map(|(provider, due)| {
+                    scope.spawn(move || match provider.update_all() {
+                        Ok(()) => (provider, due, true),
+                        Err(err) => {
+                            println!("Unable to update provider: {}", err);
+                            (provider, due, false)
+                        }
+                    })
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+
+        let mut updates = Vec::new();
+        for (provider, due, updated) in polled {
+            for sensor in provider.sensors() {
+                let unique_id = sensor.unique_id();
+                if !due.contains(&unique_id) {
+                    continue;
+                }
+                if !updated {
+                    let delay = self
+                        .retry_delays
+                        .get(&unique_id)
+                        .map_or(MIN_RETRY_DELAY, |delay| *delay * 2)
+                        .min(sensor.poll_interval());
+                    self.retry_delays.insert(unique_id.clone(), delay);
+                    self.next_polls.insert(unique_id, now + delay);
+                    continue;
+                }
+                self.retry_delays.remove(&unique_id);
+                self.next_polls
+                    .insert(unique_id.clone(), now + sensor.poll_interval());
+                let update = if provider.available() {
+                    sensor.update_info()
+                } else {
+                    sensor.unavailable_info()
+                };
+                let state = state_of(&update);
+                if should_report(
+                    sensor.report_policy(),
+                    sensor.heartbeat(),
+                    self.last_reports.get(&unique_id),
+                    &state,
+                    now,
+                ) {
+                    self.last_reports
+                        .insert(unique_id, Report { state, at: now });
+                    updates.push(update);
+                }
+            }
+        }
+        updates
+    }
+
+    /// Subsystems the providers want uevents for
+    pub fn subsystems(&self) -> Vec<&'static str> {
+        let mut subsystems: Vec<&'static str> = self
+            .providers
+            .iter()
+            .flat_map(|provider| provider.subsystems())
+            .collect();
+        subsystems.sort_unstable();
+        subsystems.dedup();
+        subsystems
+    }
+
+    /// Make the sensors of the providers subscribed to `subsystem` due now
+    pub fn trigger(&mut self, subsystem: &str) {
+        for provider in self.providers.iter() {
+            if provider.subsystems().contains(&subsystem) {
+                for sensor in provider.sensors() {
+                    self.next_polls.remove(&sensor.unique_id());
+                }
+            }
+        }
+    }
+
+    /// Force every sensor to be polled and reported on the next `poll_due`
+    pub fn reset(&mut self) {
+        self.next_polls.clear();
+        self.last_reports.clear();
+        self.retry_delays.clear();
+    }
+
+    fn next_poll(&self, sensor: &dyn DynSensor) -> Instant {
+        self.next_polls
+            .get(&sensor.unique_id())
+            .copied()
+            .unwrap_or_else(Instant::now)
+    }
+}
+
+/// State to compare the next ones to, `null` while unavailable
+fn state_of(update: &DynSensorData) -> Value {
+    match update {
+        SensorData::UpdateSensorStates { data } if data.available == Some(false) => Value::Null,
+        SensorData::UpdateSensorStates { data } => data.state.clone(),
+        SensorData::RegisterSensor { data } => data.state.clone(),
+    }
+}
+
+fn should_report(
+    policy: ReportPolicy,
+    heartbeat: Option<Duration>,
+    last: Option<&Report>,
+    state: &Value,
+    now: Instant,
+) -> bool {
+    let last = match last {
+        None => return true,
+        Some(last) => last,
+    };
+    if heartbeat.is_some_and(|heartbeat| now.duration_since(last.at) >= heartbeat) {
+        return true;
+    }
+    let delta = match (state.as_f64(), last.state.as_f64()) {
+        (Some(state), Some(last)) => Some(((state - last).abs(), last.abs())),
+        _ => None,
+    };
+    match (policy, delta) {
+        (ReportPolicy::Always, _) => true,
+        (ReportPolicy::AbsoluteThreshold(threshold), Some((delta, _))) => delta >= threshold,
+        (ReportPolicy::PercentThreshold(percent), Some((delta, last))) if last > 0.0 => {
+            delta / last * 100.0 >= percent
+        }
+        _ => *state != last.state,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{should_report, Report, Scheduler};
+    use crate::sensors::{DynSensor, Provider, ReportPolicy, Sensor};
+    use serde_json::json;
+    use std::time::{Duration, Instant};
+
+    struct CounterProvider {
+        count: u32,
+        available: bool,
+        counter: Counter,
+    }
+
+    impl Provider for CounterProvider {
+        fn new() -> Self {
+            CounterProvider {
+                count: 0,
+                available: true,
+                counter: Counter::new(),
+            }
+        }
+
+        fn update_all(&mut self) -> Result<(), &'static str> {
+            self.count += 1;
+            self.counter.state = Counter::get_current(self)?;
+            Ok(())
+        }
+
+        fn sensors(&self) -> Vec<&dyn DynSensor> {
+            vec![&self.counter]
+        }
+
+        fn subsystems(&self) -> Vec<&'static str> {
+            vec!["power_supply"]
+        }
+
+        fn available(&self) -> bool {
+            self.available
+        }
+    }
+
+    #[derive(Sensor)]
+    #[sensor(
+        provider = "CounterProvider",
+        current = "halved",
+        id = "counter",
+        name = "Counter",
+        interval = 10,
+        report = "on_change"
+    )]
+    struct Counter {
+        state: u32,
+    }
+
+    fn halved(provider: &CounterProvider) -> Result<u32, &'static str> {
+        Ok(provider.count / 2)
+    }
+
+    #[test]
+    fn poll_due_test() {
+        let mut scheduler = Scheduler::new(vec![Box::new(CounterProvider::new())]);
+        let start = Instant::now();
+        // First poll always reports
+        assert_eq!(scheduler.poll_due(start).len(), 1);
+        // Not due yet
+        assert_eq!(scheduler.poll_due(start + Duration::from_secs(5)).len(), 0);
+        assert_eq!(scheduler.next_deadline(), start + Duration::from_secs(10));
+        // Due, and the state changed from 0 to 1
+        assert_eq!(scheduler.poll_due(start + Duration::from_secs(10)).len(), 1);
+        // Due, but the state is still 1
+        assert_eq!(scheduler.poll_due(start + Duration::from_secs(20)).len(), 0);
+    }
+
+    #[test]
+    fn unavailable_test() {
+        let provider = CounterProvider {
+            available: false,
+            ..CounterProvider::new()
+        };
+        let mut scheduler = Scheduler::new(vec![Box::new(provider)]);
+        let start = Instant::now();
+        let updates = scheduler.poll_due(start);
+        assert_eq!(
+            serde_json::to_value(&updates).unwrap()[0]["data"]["available"],
+            json!(false)
+        );
+        // Still unavailable, the changing count isn't reported
+        assert_eq!(scheduler.poll_due(start + Duration::from_secs(10)).len(), 0);
+    }
+
+    struct FailingProvider {
+        counter: Counter,
+    }
+
+    impl Provider for FailingProvider {
+        fn new() -> Self {
+            FailingProvider {
+                counter: Counter::new(),
+            }
+        }
+
+        fn update_all(&mut self) -> Result<(), &'static str> {
+            Err("always failing")
+        }
+
+        fn sensors(&self) -> Vec<&dyn DynSensor> {
+            vec![&self.counter]
+        }
+    }
+
+    #[test]
+    fn failing_provider_test() {
+        let mut scheduler = Scheduler::new(vec![Box::new(FailingProvider::new())]);
+        let start = Instant::now();
+        assert_eq!(scheduler.poll_due(start).len(), 0);
+        // Retried later, not right away
+        assert_eq!(scheduler.next_deadline(), start + Duration::from_secs(5));
+        let retry = start + Duration::from_secs(5);
+        assert_eq!(scheduler.poll_due(retry).len(), 0);
+        assert_eq!(scheduler.next_deadline(), retry + Duration::from_secs(10));
+        // The backoff stops growing at the poll interval
+        let retry = retry + Duration::from_secs(10);
+        scheduler.poll_due(retry);
+        assert_eq!(scheduler.next_deadline(), retry + Duration::from_secs(10));
+    }
+
+    #[test]
+    fn trigger_test() {
+        let mut scheduler = Scheduler::new(vec![Box::new(CounterProvider::new())]);
+        let start = Instant::now();
+        assert_eq!(scheduler.subsystems(), vec!["power_supply"]);
+        assert_eq!(scheduler.poll_due(start).len(), 1);
+        scheduler.trigger("net");
+        assert_eq!(scheduler.poll_due(start + Duration::from_secs(1)).len(), 0);
+        scheduler.trigger("power_supply");
+        // Polled again out of schedule, the state went from 0 to 1
+        assert_eq!(scheduler.poll_due(start + Duration::from_secs(2)).len(), 1);
+    }
+
+    #[test]
+    fn should_report_test() {
+        let now = Instant::now();
+        let last = Report {
+            state: json!(50),
+            at: now,
+        };
+        let later = now + Duration::from_secs(60);
+        assert!(should_report(
+            ReportPolicy::OnChange,
+            None,
+            None,
+            &json!(50),
+            now
+        ));
+        assert!(should_report(
+            ReportPolicy::Always,
+            None,
+            Some(&last),
+            &json!(50),
+            later
+        ));
+        assert!(!should_report(
+            ReportPolicy::OnChange,
+            None,
+            Some(&last),
+            &json!(50),
+            later
+        ));
+        assert!(should_report(
+            ReportPolicy::OnChange,
+            None,
+            Some(&last),
+            &json!(51),
+            later
+        ));
+        assert!(!should_report(
+            ReportPolicy::AbsoluteThreshold(2.0),
+            None,
+            Some(&last),
+            &json!(51),
+            later
+        ));
+        assert!(should_report(
+            ReportPolicy::AbsoluteThreshold(2.0),
+            None,
+            Some(&last),
+            &json!(48),
+            later
+        ));
+        assert!(!should_report(
+            ReportPolicy::PercentThreshold(10.0),
+            None,
+            Some(&last),
+            &json!(54),
+            later
+        ));
+        assert!(should_report(
+            ReportPolicy::PercentThreshold(10.0),
+            None,
+            Some(&last),
+            &json!(55),
+            later
+        ));
+        assert!(should_report(
+            ReportPolicy::OnChange,
+            Some(Duration::from_secs(30)),
+            Some(&last),
+            &json!(50),
+            later
+        ));
+    }
+}
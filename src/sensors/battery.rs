@@ -1,4 +1,4 @@
-use super::{Provider, Sensor, SensorData, SensorRegisterData, SensorType, SensorUpdateData};
+use super::{DynSensor, Provider, Sensor};
 use battery::units::ratio::percent;
 use serde::Serialize;
 
@@ -8,18 +8,21 @@ pub struct BatteryProvider {
     // charge_state: String,
 }
 
+fn first_battery() -> Option<battery::Battery> {
+    battery::Manager::new().ok()?.batteries().ok()?.next()?.ok()
+}
+
+/// Whether the machine has a battery, `BatteryProvider::new` panics otherwise
+pub fn has_battery() -> bool {
+    first_battery().is_some()
+}
+
 impl Provider for BatteryProvider {
     /// Create a new BatteryProvider
     fn new() -> Self {
         let manager = battery::Manager::new().unwrap();
         BatteryProvider {
-            battery: manager
-                .batteries()
-                .unwrap()
-                .into_iter()
-                .next()
-                .unwrap()
-                .unwrap(),
+            battery: manager.batteries().unwrap().next().unwrap().unwrap(),
             level: BatteryLevel::new(),
             // charge_state: String::from(""),
         }
@@ -27,73 +30,54 @@ impl Provider for BatteryProvider {
 
     /// Update all the data
     fn update_all(&mut self) -> Result<(), &'static str> {
-        self.battery = battery::Manager::new()
-            .unwrap()
-            .batteries()
-            .unwrap()
-            .into_iter()
-            .next()
-            .unwrap()
-            .unwrap();
-        self.level.state = BatteryLevel::get_current(self).unwrap();
+        self.battery = first_battery().ok_or("No battery")?;
+        self.level.state = BatteryLevel::get_current(self)?;
         Ok(())
     }
+
+    fn sensors(&self) -> Vec<&dyn DynSensor> {
+        vec![&self.level]
+    }
+
+    fn subsystems(&self) -> Vec<&'static str> {
+        vec!["power_supply"]
+    }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "BatteryProvider",
+    current = "state_of_charge",
+    id = "battery_level",
+    name = "Battery Level",
+    device_class = "battery",
+    unit = "%",
+    icon = "mdi:battery-unknown",
+    icon_fn = "level_icon",
+    report = "on_change",
+    heartbeat = 900
+)]
 pub struct BatteryLevel {
     pub state: u8,
 }
 
-impl Sensor<BatteryProvider> for BatteryLevel {
-    type StateType = u8;
-
-    fn new() -> Self {
-        BatteryLevel { state: 0 }
-    }
-
-    fn get_current(provider: &BatteryProvider) -> Result<Self::StateType, &'static str> {
-        Ok(provider.battery.state_of_charge().get::<percent>() as u8)
-    }
-
-    fn get_register_info(&self) -> SensorData<Self::StateType> {
-        SensorData::RegisterSensor::<Self::StateType> {
-            data: SensorRegisterData {
-                r#type: SensorType::Sensor,
-                unique_id: String::from("battery_level"),
-                name: String::from("Battery Level"),
-                state: self.state,
-                device_class: Some(String::from("battery")),
-                icon: Some(String::from("mdi:battery-unknown")),
-                unit_of_measurement: Some(String::from("%")),
-                state_class: None,
-                entity_category: None,
-            },
-        }
-    }
+fn state_of_charge(provider: &BatteryProvider) -> Result<u8, &'static str> {
+    Ok(provider.battery.state_of_charge().get::<percent>() as u8)
+}
 
-    fn get_update_info(&self) -> SensorData<Self::StateType> {
-        let icon: Option<String>;
-        match self.state {
-            91..=100 => icon = Some(String::from("mdi:battery")),
-            81..=90 => icon = Some(String::from("mdi:battery-90")),
-            71..=80 => icon = Some(String::from("mdi:battery-80")),
-            61..=70 => icon = Some(String::from("mdi:battery-70")),
-            51..=60 => icon = Some(String::from("mdi:battery-60")),
-            41..=50 => icon = Some(String::from("mdi:battery-50")),
-            31..=40 => icon = Some(String::from("mdi:battery-40")),
-            21..=30 => icon = Some(String::from("mdi:battery-30")),
-            11..=20 => icon = Some(String::from("mdi:battery-20")),
-            0..=10 => icon = Some(String::from("mdi:battery-alert-outline")),
-            _ => icon = Some(String::from("mdi:battery-unknown")),
-        }
-        SensorData::UpdateSensorStates::<Self::StateType> {
-            data: SensorUpdateData {
-                r#type: SensorType::Sensor,
-                unique_id: String::from("battery_level"),
-                state: self.state,
-                icon,
-            },
-        }
-    }
+fn level_icon(level: &u8) -> String {
+    let icon = match level {
+        91..=100 => "mdi:battery",
+        81..=90 => "mdi:battery-90",
+        71..=80 => "mdi:battery-80",
+        61..=70 => "mdi:battery-70",
+        51..=60 => "mdi:battery-60",
+        41..=50 => "mdi:battery-50",
+        31..=40 => "mdi:battery-40",
+        21..=30 => "mdi:battery-30",
+        11..=20 => "mdi:battery-20",
+        0..=10 => "mdi:battery-alert-outline",
+        _ => "mdi:battery-unknown",
+    };
+    String::from(icon)
 }
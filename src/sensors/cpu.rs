@@ -1,4 +1,4 @@
-use super::{Provider, Sensor, SensorData, SensorRegisterData, SensorType, SensorUpdateData};
+use super::{DynSensor, Provider, Sensor};
 use serde::Serialize;
 use sys_info;
 
@@ -22,102 +22,47 @@ impl Provider for CpuProvider {
         self.cpu_freq.state = CpuFreq::get_current(self).unwrap();
         Ok(())
     }
+
+    fn sensors(&self) -> Vec<&dyn DynSensor> {
+        vec![&self.cpu_cores, &self.cpu_freq]
+    }
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "CpuProvider",
+    current = "cpu_speed",
+    id = "cpu_freq",
+    name = "CPU Frequency",
+    device_class = "frequency",
+    unit = "MHz",
+    icon = "mdi:sine-wave",
+    interval = 30,
+    threshold_percent = 10,
+    heartbeat = 900
+)]
 pub struct CpuFreq {
     pub state: u64,
-    unique_id: String,
 }
 
-impl Sensor<CpuProvider> for CpuFreq {
-    type StateType = u64;
-
-    fn new() -> Self {
-        CpuFreq {
-            state: 0,
-            unique_id: String::from("cpu_freq"),
-        }
-    }
-
-    #[allow(unused)]
-    fn get_current(provider: &CpuProvider) -> Result<Self::StateType, &'static str> {
-        Ok(sys_info::cpu_speed().unwrap())
-    }
-
-    fn get_register_info(&self) -> SensorData<Self::StateType> {
-        SensorData::RegisterSensor::<Self::StateType> {
-            data: SensorRegisterData {
-                r#type: SensorType::Sensor,
-                unique_id: self.unique_id.clone(),
-                name: String::from("CPU Frequency"),
-                state: self.state,
-                device_class: Some(String::from("frequency")),
-                icon: Some(String::from("mdi:sine-wave")),
-                unit_of_measurement: Some(String::from("MHz")),
-                state_class: None,
-                entity_category: None,
-            },
-        }
-    }
-
-    fn get_update_info(&self) -> SensorData<Self::StateType> {
-        SensorData::UpdateSensorStates::<Self::StateType> {
-            data: SensorUpdateData {
-                r#type: SensorType::Sensor,
-                unique_id: self.unique_id.clone(),
-                state: self.state,
-                icon: Some(String::from("mdi:sine-wave")),
-            },
-        }
-    }
+fn cpu_speed(_provider: &CpuProvider) -> Result<u64, &'static str> {
+    sys_info::cpu_speed().map_err(|_| "Unable to read CPU frequency")
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "CpuProvider",
+    current = "cpu_num",
+    id = "cpu_cores",
+    name = "CPU Cores",
+    icon = "mdi:memory",
+    interval = 3600,
+    report = "on_change"
+)]
 pub struct CpuCores {
     pub state: u32,
-    unique_id: String,
 }
 
-impl Sensor<CpuProvider> for CpuCores {
-    type StateType = u32;
-
-    fn new() -> Self {
-        CpuCores {
-            state: 0,
-            unique_id: String::from("cpu_cores"),
-        }
-    }
-
-    #[allow(unused)]
-    fn get_current(provider: &CpuProvider) -> Result<Self::StateType, &'static str> {
-        Ok(sys_info::cpu_num().unwrap())
-    }
-
-    fn get_register_info(&self) -> SensorData<Self::StateType> {
-        SensorData::RegisterSensor::<Self::StateType> {
-            data: SensorRegisterData {
-                r#type: SensorType::Sensor,
-                unique_id: self.unique_id.clone(),
-                name: String::from("CPU Cores"),
-                state: self.state,
-                device_class: None,
-                icon: Some(String::from("mdi:memory")),
-                unit_of_measurement: None,
-                state_class: None,
-                entity_category: None,
-            },
-        }
-    }
-
-    fn get_update_info(&self) -> SensorData<Self::StateType> {
-        SensorData::UpdateSensorStates::<Self::StateType> {
-            data: SensorUpdateData {
-                r#type: SensorType::Sensor,
-                unique_id: self.unique_id.clone(),
-                state: self.state,
-                icon: Some(String::from("mdi:memory")),
-            },
-        }
-    }
+fn cpu_num(_provider: &CpuProvider) -> Result<u32, &'static str> {
+    sys_info::cpu_num().map_err(|_| "Unable to read CPU cores count")
 }
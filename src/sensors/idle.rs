@@ -0,0 +1,182 @@
+use super::{DynSensor, Provider, Sensor};
+use crate::logind::{self, SessionProxyBlocking};
+use serde::Serialize;
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    mem,
+    os::unix::io::AsRawFd,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+const INPUT_DIR: &str = "/dev/input";
+
+/// Time since the last user input.
+///
+/// Read from the events of the `/dev/input/event*` devices when they can be
+/// opened (`input` group), else from the logind `IdleHint`/`IdleSinceHint`
+/// of the display session, which some desktops never set.
+pub struct IdleProvider {
+    session: Option<SessionProxyBlocking<'static>>,
+    /// Watcher of the input devices, started once
+    input: Option<Arc<InputWatch>>,
+    idle_for: Option<Duration>,
+    pub idle_time: IdleTime,
+}
+
+/// Shared with the thread reading the input events
+struct InputWatch {
+    /// Unix time in microseconds of the last input event
+    last_input: AtomicU64,
+    /// Cleared when the thread stops, on unplug or read errors
+    running: AtomicBool,
+}
+
+impl IdleProvider {
+    fn logind_idle(&mut self, now: SystemTime) -> zbus::Result<Duration> {
+        if self.session.is_none() {
+            self.session = Some(logind::display_session_blocking()?);
+        }
+        let session = self.session.as_ref().unwrap();
+        if !session.idle_hint()? {
+            return Ok(Duration::ZERO);
+        }
+        let since = UNIX_EPOCH + Duration::from_micros(session.idle_since_hint()?);
+        Ok(now.duration_since(since).unwrap_or_default())
+    }
+
+    fn input_idle(&self, now: SystemTime) -> Option<Duration> {
+        let input = self
+            .input
+            .as_ref()
+            .filter(|input| input.running.load(Ordering::Relaxed))?;
+        let last_input =
+            UNIX_EPOCH + Duration::from_micros(input.last_input.load(Ordering::Relaxed));
+        Some(now.duration_since(last_input).unwrap_or_default())
+    }
+}
+
+impl Provider for IdleProvider {
+    fn new() -> Self {
+        IdleProvider {
+            session: None,
+            input: watch_input(),
+            idle_for: None,
+            idle_time: IdleTime::new(),
+        }
+    }
+
+    fn update_all(&mut self) -> Result<(), &'static str> {
+        let now = SystemTime::now();
+        self.idle_for = match self.input_idle(now) {
+            Some(idle_for) => Some(idle_for),
+            None => match self.logind_idle(now) {
+                Ok(idle_for) => Some(idle_for),
+                Err(_) => {
+                    self.session = None;
+                    None
+                }
+            },
+        };
+        self.idle_time.state = IdleTime::get_current(self)?;
+        Ok(())
+    }
+
+    fn sensors(&self) -> Vec<&dyn DynSensor> {
+        vec![&self.idle_time]
+    }
+}
+
+fn unix_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_micros() as u64
+}
+
+/// Read the events of every input device that can be opened in a
+/// background thread, `None` when none can be
+fn watch_input() -> Option<Arc<InputWatch>> {
+    let mut devices: Vec<File> = fs::read_dir(INPUT_DIR)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("event"))
+        .filter_map(|entry| File::open(entry.path()).ok())
+        .collect();
+    if devices.is_empty() {
+        return None;
+    }
+    let input = Arc::new(InputWatch {
+        // Counted from now until the first event
+        last_input: AtomicU64::new(unix_micros(SystemTime::now())),
+        running: AtomicBool::new(true),
+    });
+    let watch = input.clone();
+    thread::spawn(move || {
+        let mut event = [0u8; mem::size_of::<libc::input_event>()];
+        while !devices.is_empty() {
+            let mut fds: Vec<libc::pollfd> = devices
+                .iter()
+                .map(|device| libc::pollfd {
+                    fd: device.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                })
+                .collect();
+            let res = unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, -1) };
+            if res < 0 {
+                if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                break;
+            }
+            let mut unplugged = Vec::new();
+            for (i, fd) in fds.iter().enumerate() {
+                if fd.revents & (libc::POLLERR | libc::POLLHUP | libc::POLLNVAL) != 0 {
+                    unplugged.push(i);
+                } else if fd.revents & libc::POLLIN != 0 {
+                    match devices[i].read_exact(&mut event) {
+                        Ok(()) => watch
+                            .last_input
+                            .store(unix_micros(SystemTime::now()), Ordering::Relaxed),
+                        Err(_) => unplugged.push(i),
+                    }
+                }
+            }
+            for i in unplugged.into_iter().rev() {
+                devices.remove(i);
+            }
+        }
+        watch.running.store(false, Ordering::Relaxed);
+    });
+    Some(input)
+}
+
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "IdleProvider",
+    current = "idle_seconds",
+    id = "idle_time",
+    name = "Idle Time",
+    device_class = "duration",
+    unit = "s",
+    state_class = "measurement",
+    icon = "mdi:timer-sand",
+    interval = 10,
+    threshold = 60,
+    heartbeat = 900
+)]
+pub struct IdleTime {
+    pub state: u64,
+}
+
+fn idle_seconds(provider: &IdleProvider) -> Result<u64, &'static str> {
+    provider
+        .idle_for
+        .map(|idle_for| idle_for.as_secs())
+        .ok_or("Unable to read the idle time")
+}
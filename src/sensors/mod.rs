@@ -1,11 +1,35 @@
 use serde::Serialize;
+use serde_json::Value;
+use std::time::Duration;
 
 pub mod battery;
 pub mod cpu;
+pub mod idle;
+pub mod network;
+pub mod session;
+pub mod sleep;
+pub mod storage;
+pub mod system;
 
-pub trait Provider {
-    fn new() -> Self;
+pub use rusty_hass_laptop_derive::Sensor;
+
+pub trait Provider: Send {
+    fn new() -> Self
+    where
+        Self: Sized;
     fn update_all(&mut self) -> Result<(), &'static str>;
+    /// All the sensors exposed by this provider
+    fn sensors(&self) -> Vec<&dyn DynSensor>;
+    /// Kernel subsystems (`power_supply`, `net`, `block`, ...) whose uevents
+    /// should trigger an immediate refresh, on top of the regular polling
+    fn subsystems(&self) -> Vec<&'static str> {
+        Vec::new()
+    }
+    /// Whether the sensors have a state, they are reported unavailable
+    /// otherwise
+    fn available(&self) -> bool {
+        true
+    }
 }
 
 pub trait Sensor<T: Provider> {
@@ -17,6 +41,58 @@ pub trait Sensor<T: Provider> {
     fn get_update_info(&self) -> SensorData<Self::StateType>;
 }
 
+/// Object safe version of [`Sensor`], with the state serialised to json.
+///
+/// Implemented by `#[derive(Sensor)]`, so sensors of different types can be
+/// stored together and registered/updated in a loop.
+pub trait DynSensor {
+    fn unique_id(&self) -> String;
+    fn register_info(&self) -> DynSensorData;
+    fn update_info(&self) -> DynSensorData;
+
+    /// How often the sensor should be refreshed
+    fn poll_interval(&self) -> Duration {
+        DEFAULT_POLL_INTERVAL
+    }
+
+    /// When a refreshed state should be sent to Home Assistant
+    fn report_policy(&self) -> ReportPolicy {
+        ReportPolicy::Always
+    }
+
+    /// Send the state at least this often, whatever the report policy says
+    fn heartbeat(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Update marking the sensor as unavailable, sent when the app stops
+    fn unavailable_info(&self) -> DynSensorData {
+        match self.update_info() {
+            SensorData::UpdateSensorStates { mut data } => {
+                data.available = Some(false);
+                SensorData::UpdateSensorStates { data }
+            }
+            register => register,
+        }
+    }
+}
+
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportPolicy {
+    /// Every poll is reported
+    Always,
+    /// Only report when the state differs from the last reported one
+    OnChange,
+    /// Only report when a numeric state moved by at least this amount
+    AbsoluteThreshold(f64),
+    /// Only report when a numeric state moved by at least this percentage
+    PercentThreshold(f64),
+}
+
+pub type DynSensorData = SensorData<Value>;
+
 #[derive(Serialize)]
 #[serde(rename_all = "snake_case")]
 pub enum SensorType {
@@ -32,6 +108,36 @@ pub enum SensorData<T: Serialize> {
     RegisterSensor { data: SensorRegisterData<T> },
 }
 
+impl<T: Serialize> SensorData<T> {
+    /// Serialise the state to a json value
+    pub fn into_dyn(self) -> DynSensorData {
+        match self {
+            SensorData::UpdateSensorStates { data } => SensorData::UpdateSensorStates {
+                data: SensorUpdateData {
+                    r#type: data.r#type,
+                    unique_id: data.unique_id,
+                    state: serde_json::to_value(data.state).unwrap(),
+                    icon: data.icon,
+                    available: data.available,
+                },
+            },
+            SensorData::RegisterSensor { data } => SensorData::RegisterSensor {
+                data: SensorRegisterData {
+                    r#type: data.r#type,
+                    unique_id: data.unique_id,
+                    name: data.name,
+                    state: serde_json::to_value(data.state).unwrap(),
+                    device_class: data.device_class,
+                    icon: data.icon,
+                    unit_of_measurement: data.unit_of_measurement,
+                    state_class: data.state_class,
+                    entity_category: data.entity_category,
+                },
+            },
+        }
+    }
+}
+
 #[derive(Serialize)]
 pub struct SensorRegisterData<T: Serialize> {
     pub r#type: SensorType,
@@ -57,12 +163,14 @@ pub struct SensorUpdateData<T: Serialize> {
     pub state: T,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub icon: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available: Option<bool>,
 }
 
 #[cfg(test)]
 mod tests {
 
-    use super::{battery, Sensor};
+    use super::{battery, cpu, DynSensor, Sensor};
     use super::{Provider, SensorData, SensorType, SensorUpdateData};
 
     #[test]
@@ -73,6 +181,7 @@ mod tests {
                 unique_id: String::from("battery_level"),
                 state: 32,
                 icon: None,
+                available: None,
             },
         };
         println!("serialized = {}", serde_json::to_string(&req).unwrap());
@@ -89,4 +198,38 @@ mod tests {
             serde_json::to_string(&bat_info.level.get_update_info()).unwrap()
         );
     }
+    #[test]
+    fn derive_test() {
+        let freq = cpu::CpuFreq { state: 2400 };
+        assert_eq!(
+            serde_json::to_string(&freq.get_register_info()).unwrap(),
+            r#"{"type":"register_sensor","data":{"type":"sensor","unique_id":"cpu_freq","name":"CPU Frequency","state":2400,"device_class":"frequency","icon":"mdi:sine-wave","unit_of_measurement":"MHz"}}"#
+        );
+        let level = battery::BatteryLevel { state: 85 };
+        assert_eq!(
+            serde_json::to_string(&level.get_update_info()).unwrap(),
+            r#"{"type":"update_sensor_states","data":{"type":"sensor","unique_id":"battery_level","state":85,"icon":"mdi:battery-90"}}"#
+        );
+    }
+    #[test]
+    fn dyn_sensor_test() {
+        let freq = cpu::CpuFreq { state: 2400 };
+        let cores = cpu::CpuCores { state: 8 };
+        let sensors: Vec<&dyn DynSensor> = vec![&freq, &cores];
+        let ids: Vec<String> = sensors.iter().map(|s| s.unique_id()).collect();
+        assert_eq!(ids, vec!["cpu_freq", "cpu_cores"]);
+        assert_eq!(
+            serde_json::to_string(&cores.update_info()).unwrap(),
+            serde_json::to_string(&cores.get_update_info()).unwrap()
+        );
+    }
+
+    #[test]
+    fn unavailable_test() {
+        let cores = cpu::CpuCores { state: 8 };
+        assert_eq!(
+            serde_json::to_string(&cores.unavailable_info()).unwrap(),
+            r#"{"type":"update_sensor_states","data":{"type":"sensor","unique_id":"cpu_cores","state":8,"icon":"mdi:memory","available":false}}"#
+        );
+    }
 }
@@ -0,0 +1,80 @@
+use super::{DynSensor, Provider, Sensor};
+use serde::Serialize;
+use std::{fs, path::Path};
+
+const NET_CLASS_DIR: &str = "/sys/class/net";
+
+/// Network connectivity, refreshed on the `net` uevents
+pub struct NetworkProvider {
+    pub connected: NetworkConnected,
+}
+
+impl Provider for NetworkProvider {
+    fn new() -> Self {
+        NetworkProvider {
+            connected: NetworkConnected::new(),
+        }
+    }
+
+    fn update_all(&mut self) -> Result<(), &'static str> {
+        self.connected.state = NetworkConnected::get_current(self)?;
+        Ok(())
+    }
+
+    fn sensors(&self) -> Vec<&dyn DynSensor> {
+        vec![&self.connected]
+    }
+
+    fn subsystems(&self) -> Vec<&'static str> {
+        vec!["net"]
+    }
+}
+
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "NetworkProvider",
+    current = "is_connected",
+    id = "network_connected",
+    name = "Network Connected",
+    device_class = "connectivity",
+    binary,
+    interval = 60,
+    report = "on_change",
+    heartbeat = 900
+)]
+pub struct NetworkConnected {
+    pub state: bool,
+}
+
+/// Whether an interface other than the loopback is up
+fn any_interface_up(net_dir: &Path) -> Result<bool, &'static str> {
+    let interfaces = fs::read_dir(net_dir).map_err(|_| "Unable to list network interfaces")?;
+    Ok(interfaces.flatten().any(|interface| {
+        interface.file_name() != "lo"
+            && fs::read_to_string(interface.path().join("operstate"))
+                .is_ok_and(|state| state.trim() == "up")
+    }))
+}
+
+fn is_connected(_provider: &NetworkProvider) -> Result<bool, &'static str> {
+    any_interface_up(Path::new(NET_CLASS_DIR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::any_interface_up;
+    use std::fs;
+
+    #[test]
+    fn interface_up_test() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for (interface, state) in [("lo", "unknown"), ("wlan0", "down")] {
+            fs::create_dir_all(dir.join(interface)).unwrap();
+            fs::write(dir.join(interface).join("operstate"), state).unwrap();
+        }
+        assert!(!any_interface_up(dir).unwrap());
+        fs::write(dir.join("wlan0").join("operstate"), "up\n").unwrap();
+        assert!(any_interface_up(dir).unwrap());
+    }
+}
@@ -0,0 +1,153 @@
+use super::{DynSensor, Provider, Sensor};
+use crate::logind::{self, SessionProxyBlocking};
+use serde::Serialize;
+
+/// Lock/idle/active state of the user graphical session, read from logind.
+///
+/// Without a display session (service started before login, SSH only, root)
+/// the sensors are reported unavailable.
+pub struct SessionProvider {
+    session: Option<SessionProxyBlocking<'static>>,
+    hints: Option<SessionHints>,
+    /// Whether the missing session was already logged
+    missing: bool,
+    pub locked: SessionLocked,
+    pub idle: SessionIdle,
+    pub active: SessionActive,
+}
+
+struct SessionHints {
+    locked: bool,
+    idle: bool,
+    active: bool,
+}
+
+impl SessionProvider {
+    fn session(&mut self) -> zbus::Result<&SessionProxyBlocking<'static>> {
+        if self.session.is_none() {
+            self.session = Some(logind::display_session_blocking()?);
+        }
+        Ok(self.session.as_ref().unwrap())
+    }
+
+    fn read_hints(&mut self) -> zbus::Result<SessionHints> {
+        let session = self.session()?;
+        Ok(SessionHints {
+            locked: session.locked_hint()?,
+            idle: session.idle_hint()?,
+            active: session.active()?,
+        })
+    }
+}
+
+impl Provider for SessionProvider {
+    /// The D-Bus connection is only opened on the first update
+    fn new() -> Self {
+        SessionProvider {
+            session: None,
+            hints: None,
+            missing: false,
+            locked: SessionLocked::new(),
+            idle: SessionIdle::new(),
+            active: SessionActive::new(),
+        }
+    }
+
+    fn update_all(&mut self) -> Result<(), &'static str> {
+        match self.read_hints() {
+            Ok(hints) => {
+                self.hints = Some(hints);
+                self.missing = false;
+            }
+            Err(err) => {
+                // The session may have ended, look it up again next time
+                self.session = None;
+                self.hints = None;
+                if !self.missing {
+                    println!(
+                        "No logind display session, the session sensors are unavailable: {}",
+                        err
+                    );
+                    self.missing = true;
+                }
+                return Ok(());
+            }
+        }
+        self.locked.state = SessionLocked::get_current(self)?;
+        self.idle.state = SessionIdle::get_current(self)?;
+        self.active.state = SessionActive::get_current(self)?;
+        Ok(())
+    }
+
+    fn sensors(&self) -> Vec<&dyn DynSensor> {
+        vec![&self.locked, &self.idle, &self.active]
+    }
+
+    fn available(&self) -> bool {
+        self.hints.is_some()
+    }
+}
+
+fn hints(provider: &SessionProvider) -> Result<&SessionHints, &'static str> {
+    provider.hints.as_ref().ok_or("Session not read yet")
+}
+
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "SessionProvider",
+    current = "is_locked",
+    id = "session_locked",
+    name = "Locked",
+    icon = "mdi:monitor-lock",
+    binary,
+    interval = 10,
+    report = "on_change",
+    heartbeat = 900
+)]
+pub struct SessionLocked {
+    pub state: bool,
+}
+
+fn is_locked(provider: &SessionProvider) -> Result<bool, &'static str> {
+    Ok(hints(provider)?.locked)
+}
+
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "SessionProvider",
+    current = "is_idle",
+    id = "session_idle",
+    name = "Idle",
+    icon = "mdi:sleep",
+    binary,
+    interval = 10,
+    report = "on_change",
+    heartbeat = 900
+)]
+pub struct SessionIdle {
+    pub state: bool,
+}
+
+fn is_idle(provider: &SessionProvider) -> Result<bool, &'static str> {
+    Ok(hints(provider)?.idle)
+}
+
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "SessionProvider",
+    current = "is_active",
+    id = "session_active",
+    name = "Session Active",
+    icon = "mdi:account-check",
+    binary,
+    interval = 10,
+    report = "on_change",
+    heartbeat = 900
+)]
+pub struct SessionActive {
+    pub state: bool,
+}
+
+fn is_active(provider: &SessionProvider) -> Result<bool, &'static str> {
+    Ok(hints(provider)?.active)
+}
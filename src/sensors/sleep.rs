@@ -0,0 +1,55 @@
+use super::{DynSensor, Provider, Sensor};
+use serde::Serialize;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Sleep state, set from the logind `PrepareForSleep` signal
+pub struct SleepProvider {
+    state: Arc<AtomicBool>,
+    pub sleeping: Sleeping,
+}
+
+impl SleepProvider {
+    /// Shared flag to update when the system goes to sleep or resumes
+    pub fn handle(&self) -> Arc<AtomicBool> {
+        self.state.clone()
+    }
+}
+
+impl Provider for SleepProvider {
+    fn new() -> Self {
+        SleepProvider {
+            state: Arc::new(AtomicBool::new(false)),
+            sleeping: Sleeping::new(),
+        }
+    }
+
+    fn update_all(&mut self) -> Result<(), &'static str> {
+        self.sleeping.state = Sleeping::get_current(self)?;
+        Ok(())
+    }
+
+    fn sensors(&self) -> Vec<&dyn DynSensor> {
+        vec![&self.sleeping]
+    }
+}
+
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "SleepProvider",
+    current = "is_sleeping",
+    id = "sleeping",
+    name = "Sleeping",
+    icon = "mdi:sleep",
+    binary,
+    report = "on_change"
+)]
+pub struct Sleeping {
+    pub state: bool,
+}
+
+fn is_sleeping(provider: &SleepProvider) -> Result<bool, &'static str> {
+    Ok(provider.state.load(Ordering::Relaxed))
+}
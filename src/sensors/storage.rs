@@ -0,0 +1,85 @@
+use super::{DynSensor, Provider, Sensor};
+use serde::Serialize;
+use std::{fs, path::Path};
+
+const BLOCK_CLASS_DIR: &str = "/sys/class/block";
+
+/// Removable drives plugged in, refreshed on the `block` uevents
+pub struct StorageProvider {
+    pub removable: RemovableDrives,
+}
+
+impl Provider for StorageProvider {
+    fn new() -> Self {
+        StorageProvider {
+            removable: RemovableDrives::new(),
+        }
+    }
+
+    fn update_all(&mut self) -> Result<(), &'static str> {
+        self.removable.state = RemovableDrives::get_current(self)?;
+        Ok(())
+    }
+
+    fn sensors(&self) -> Vec<&dyn DynSensor> {
+        vec![&self.removable]
+    }
+
+    fn subsystems(&self) -> Vec<&'static str> {
+        vec!["block"]
+    }
+}
+
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "StorageProvider",
+    current = "removable_drives",
+    id = "removable_drives",
+    name = "Removable Drives",
+    icon = "mdi:usb-flash-drive",
+    interval = 300,
+    report = "on_change"
+)]
+pub struct RemovableDrives {
+    pub state: u32,
+}
+
+/// Removable disks holding a medium (partitions have no `removable` file)
+fn count_removable(block_dir: &Path) -> Result<u32, &'static str> {
+    let read = |path: &Path| fs::read_to_string(path).unwrap_or_default();
+    let devices = fs::read_dir(block_dir).map_err(|_| "Unable to list block devices")?;
+    Ok(devices
+        .flatten()
+        .filter(|device| {
+            read(&device.path().join("removable")).trim() == "1"
+                && read(&device.path().join("size")).trim() != "0"
+        })
+        .count() as u32)
+}
+
+fn removable_drives(_provider: &StorageProvider) -> Result<u32, &'static str> {
+    count_removable(Path::new(BLOCK_CLASS_DIR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::count_removable;
+    use std::fs;
+
+    #[test]
+    fn removable_test() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        for (device, removable, size) in [
+            ("nvme0n1", "0", "1000"),
+            ("sda", "1", "64"),
+            ("sr0", "1", "0"),
+        ] {
+            fs::create_dir_all(dir.join(device)).unwrap();
+            fs::write(dir.join(device).join("removable"), removable).unwrap();
+            fs::write(dir.join(device).join("size"), size).unwrap();
+        }
+        fs::create_dir_all(dir.join("sda1")).unwrap();
+        assert_eq!(count_removable(dir).unwrap(), 1);
+    }
+}
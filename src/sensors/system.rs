@@ -0,0 +1,43 @@
+use super::{DynSensor, Provider, Sensor};
+use serde::Serialize;
+use sys_info;
+
+pub struct SystemProvider {
+    pub kernel_version: KernelVersion,
+}
+
+impl Provider for SystemProvider {
+    fn new() -> Self {
+        SystemProvider {
+            kernel_version: KernelVersion::new(),
+        }
+    }
+
+    fn update_all(&mut self) -> Result<(), &'static str> {
+        self.kernel_version.state = KernelVersion::get_current(self)?;
+        Ok(())
+    }
+
+    fn sensors(&self) -> Vec<&dyn DynSensor> {
+        vec![&self.kernel_version]
+    }
+}
+
+#[derive(Sensor, Serialize, Debug)]
+#[sensor(
+    provider = "SystemProvider",
+    current = "kernel_release",
+    id = "kernel_version",
+    name = "Kernel Version",
+    icon = "mdi:linux",
+    entity_category = "diagnostic",
+    interval = 3600,
+    report = "on_change"
+)]
+pub struct KernelVersion {
+    pub state: String,
+}
+
+fn kernel_release(_provider: &SystemProvider) -> Result<String, &'static str> {
+    sys_info::os_release().map_err(|_| "Unable to read the kernel version")
+}
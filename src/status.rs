@@ -0,0 +1,73 @@
+//! Result of the last sensor updates, written by the daemon and shown by the
+//! `status` command.
+
+use crate::Config;
+use serde::{Deserialize, Serialize};
+use std::{
+    fs::{self, File},
+    io::BufReader,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct Status {
+    /// Unix timestamp of the last updates sent to Home Assistant
+    pub last_update: Option<u64>,
+    /// Number of updates sent that time
+    pub updates: usize,
+    /// Error of the last updates, if they failed
+    pub error: Option<String>,
+}
+
+impl Status {
+    /// One status file per config file, so daemons run with different
+    /// configs don't mix up their updates
+    fn path(config: &Config) -> Option<PathBuf> {
+        directories::ProjectDirs::from("fr", "obito1903", env!("CARGO_PKG_NAME")).map(|dirs| {
+            dirs.cache_dir()
+                .join(format!("status-{}.json", config.file_key()))
+        })
+    }
+
+    pub fn load(config: &Config) -> Option<Self> {
+        let file = File::open(Status::path(config)?).ok()?;
+        serde_json::from_reader(BufReader::new(file)).ok()
+    }
+
+    /// Record the result of sending `updates` updates
+    pub fn record<E: ToString>(updates: usize, result: &Result<(), E>) -> Self {
+        Status {
+            last_update: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs()),
+            updates,
+            error: result.as_ref().err().map(ToString::to_string),
+        }
+    }
+
+    pub fn save(&self, config: &Config) -> Result<(), &'static str> {
+        let path = Status::path(config).ok_or("No cache directory")?;
+        fs::create_dir_all(path.parent().unwrap())
+            .map_err(|_| "Unable to create the cache directory")?;
+        let file = File::create(path).map_err(|_| "Unable to write the status file")?;
+        serde_json::to_writer(file, self).map_err(|_| "Unable to write the status file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Status;
+
+    #[test]
+    fn record_test() {
+        let ok = Status::record::<String>(3, &Ok(()));
+        assert_eq!(ok.updates, 3);
+        assert!(ok.last_update.is_some());
+        assert_eq!(ok.error, None);
+
+        let failed = Status::record(2, &Err("timed out"));
+        assert_eq!(failed.error.as_deref(), Some("timed out"));
+    }
+}
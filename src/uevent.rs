@@ -0,0 +1,176 @@
+use std::{
+    collections::HashMap,
+    io, mem,
+    os::unix::io::{AsRawFd, FromRawFd, OwnedFd},
+    thread,
+};
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+/// Multicast group the kernel sends its uevents to
+const KERNEL_GROUP: u32 = 1;
+/// Receive buffer asked for, so bursts (docking, resume) don't overflow it
+const RECV_BUFFER_SIZE: libc::c_int = 1024 * 1024;
+
+/// A kernel uevent, as sent on the `NETLINK_KOBJECT_UEVENT` socket
+#[derive(Debug, Clone, PartialEq)]
+pub struct Uevent {
+    pub action: String,
+    pub devpath: String,
+    pub subsystem: String,
+    pub properties: HashMap<String, String>,
+}
+
+impl Uevent {
+    /// Parse a raw `action@devpath\0KEY=VALUE\0...` message
+    pub fn parse(buf: &[u8]) -> Option<Self> {
+        let mut fields = buf
+            .split(|b| *b == 0)
+            .filter(|field| !field.is_empty())
+            .map(String::from_utf8_lossy);
+        let header = fields.next()?;
+        // Messages re-broadcast by udev start with "libudev" and a binary header
+        if header == "libudev" {
+            return None;
+        }
+        let (action, devpath) = header.split_once('@')?;
+        let properties: HashMap<String, String> = fields
+            .filter_map(|field| {
+                field
+                    .split_once('=')
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+            })
+            .collect();
+        Some(Uevent {
+            action: action.to_string(),
+            devpath: devpath.to_string(),
+            subsystem: properties.get("SUBSYSTEM").cloned().unwrap_or_default(),
+            properties,
+        })
+    }
+}
+
+/// Netlink socket subscribed to the kernel uevents
+pub struct UeventSocket {
+    fd: OwnedFd,
+}
+
+impl UeventSocket {
+    pub fn open() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(
+                libc::AF_NETLINK,
+                libc::SOCK_DGRAM | libc::SOCK_CLOEXEC,
+                libc::NETLINK_KOBJECT_UEVENT,
+            )
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        // SO_RCVBUFFORCE goes over `rmem_max` but needs CAP_NET_ADMIN, a
+        // failure of the fallback only leaves the default size
+        let size = RECV_BUFFER_SIZE;
+        for option in [libc::SO_RCVBUFFORCE, libc::SO_RCVBUF] {
+            let res = unsafe {
+                libc::setsockopt(
+                    fd.as_raw_fd(),
+                    libc::SOL_SOCKET,
+                    option,
+                    &size as *const libc::c_int as *const libc::c_void,
+                    mem::size_of::<libc::c_int>() as libc::socklen_t,
+                )
+            };
+            if res == 0 {
+                break;
+            }
+        }
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        addr.nl_groups = KERNEL_GROUP;
+        let res = unsafe {
+            libc::bind(
+                fd.as_raw_fd(),
+                &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if res < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(UeventSocket { fd })
+    }
+
+    /// Block until the next uevent, `None` if the message couldn't be parsed
+    pub fn recv(&self) -> io::Result<Option<Uevent>> {
+        let mut buf = [0u8; 8192];
+        let len = unsafe {
+            libc::recv(
+                self.fd.as_raw_fd(),
+                buf.as_mut_ptr() as *mut libc::c_void,
+                buf.len(),
+                0,
+            )
+        };
+        if len < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Uevent::parse(&buf[..len as usize]))
+    }
+}
+
+/// Event standing for the uevents of `subsystem` dropped by the kernel
+fn overflow_event(subsystem: &str) -> Uevent {
+    Uevent {
+        action: String::from("change"),
+        devpath: String::new(),
+        subsystem: subsystem.to_string(),
+        properties: HashMap::new(),
+    }
+}
+
+/// Listen for uevents of the given subsystems in a background thread
+pub fn spawn_listener(subsystems: Vec<&'static str>) -> io::Result<UnboundedReceiver<Uevent>> {
+    let socket = UeventSocket::open()?;
+    let (sender, receiver) = mpsc::unbounded_channel();
+    thread::spawn(move || loop {
+        let events = match socket.recv() {
+            Ok(Some(event)) if subsystems.contains(&event.subsystem.as_str()) => vec![event],
+            Ok(_) => continue,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            // The buffer overflowed and events were lost, refresh everything
+            Err(err) if err.raw_os_error() == Some(libc::ENOBUFS) => subsystems
+                .iter()
+                .map(|subsystem| overflow_event(subsystem))
+                .collect(),
+            Err(err) => {
+                println!("Uevent listener stopped: {}", err);
+                break;
+            }
+        };
+        if events.into_iter().any(|event| sender.send(event).is_err()) {
+            break;
+        }
+    });
+    Ok(receiver)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Uevent;
+
+    #[test]
+    fn parse_test() {
+        let event = Uevent::parse(
+            b"change@/devices/LNXSYSTM:00/ACPI0003:00/power_supply/AC\0ACTION=change\0DEVPATH=/devices/LNXSYSTM:00/ACPI0003:00/power_supply/AC\0SUBSYSTEM=power_supply\0POWER_SUPPLY_ONLINE=1\0SEQNUM=4242\0",
+        )
+        .unwrap();
+        assert_eq!(event.action, "change");
+        assert_eq!(event.subsystem, "power_supply");
+        assert_eq!(
+            event.devpath,
+            "/devices/LNXSYSTM:00/ACPI0003:00/power_supply/AC"
+        );
+        assert_eq!(event.properties["POWER_SUPPLY_ONLINE"], "1");
+        assert_eq!(Uevent::parse(b"libudev\0\xfe\xed\xca\xfe"), None);
+    }
+}
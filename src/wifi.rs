@@ -0,0 +1,87 @@
+//! Wi-Fi access point the laptop is connected to, from NetworkManager
+
+use zbus::{proxy, zvariant::OwnedObjectPath, Connection};
+
+/// `NM_DEVICE_TYPE_WIFI`
+const DEVICE_TYPE_WIFI: u32 = 2;
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager",
+    default_service = "org.freedesktop.NetworkManager",
+    default_path = "/org/freedesktop/NetworkManager"
+)]
+trait NetworkManager {
+    fn get_devices(&self) -> zbus::Result<Vec<OwnedObjectPath>>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Device",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait Device {
+    #[zbus(property)]
+    fn device_type(&self) -> zbus::Result<u32>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.Device.Wireless",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait Wireless {
+    #[zbus(property)]
+    fn active_access_point(&self) -> zbus::Result<OwnedObjectPath>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.NetworkManager.AccessPoint",
+    default_service = "org.freedesktop.NetworkManager"
+)]
+trait AccessPoint {
+    #[zbus(property)]
+    fn ssid(&self) -> zbus::Result<Vec<u8>>;
+
+    #[zbus(property)]
+    fn hw_address(&self) -> zbus::Result<String>;
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccessPoint {
+    pub ssid: String,
+    /// Lowercase `aa:bb:cc:dd:ee:ff`
+    pub bssid: String,
+}
+
+/// Access point of the first connected Wi-Fi device, if any
+pub async fn active_access_point(connection: &Connection) -> zbus::Result<Option<AccessPoint>> {
+    let devices = NetworkManagerProxy::new(connection)
+        .await?
+        .get_devices()
+        .await?;
+    for path in devices {
+        let device = DeviceProxy::builder(connection)
+            .path(path.clone())?
+            .build()
+            .await?;
+        if device.device_type().await? != DEVICE_TYPE_WIFI {
+            continue;
+        }
+        let wireless = WirelessProxy::builder(connection)
+            .path(path)?
+            .build()
+            .await?;
+        let access_point = wireless.active_access_point().await?;
+        // "/" when not connected
+        if access_point.as_str() == "/" {
+            continue;
+        }
+        let access_point = AccessPointProxy::builder(connection)
+            .path(access_point)?
+            .build()
+            .await?;
+        return Ok(Some(AccessPoint {
+            ssid: String::from_utf8_lossy(&access_point.ssid().await?).into_owned(),
+            bssid: access_point.hw_address().await?.to_lowercase(),
+        }));
+    }
+    Ok(None)
+}